@@ -10,6 +10,7 @@
 
 use crate::sed::error_handling::{ScriptLocation, runtime_error};
 use crate::sed::fast_regex::{Captures, Match, Regex};
+use crate::sed::named_reader::NamedReader;
 use crate::sed::named_writer::NamedWriter;
 use crate::sed::script_char_provider::ScriptCharProvider;
 use crate::sed::script_line_provider::ScriptLineProvider;
@@ -40,7 +41,7 @@ pub struct ProcessingContext {
     pub null_data: bool,
 
     // Other context
-    /// Currently processed input file name (not script) in quoted form
+    /// Currently processed input file name (not script), - for stdin
     pub input_name: String,
     /// Current input line number
     pub line_number: usize,
@@ -48,6 +49,9 @@ pub struct ProcessingContext {
     pub last_address: bool,
     /// True if the line read is the last line
     pub last_line: bool,
+    /// True if the script needs the last line to be identified;
+    /// otherwise input is not read ahead to do this
+    pub needs_last_line: bool,
     /// True if the file is the last file of the ones specified
     pub last_file: bool,
     /// Stop processing further input.
@@ -70,48 +74,121 @@ pub struct ProcessingContext {
     pub append_elements: Vec<AppendElement>,
 }
 
+impl ProcessingContext {
+    /// Return the character that separates input and output lines.
+    pub fn separator(&self) -> char {
+        if self.null_data { '\0' } else { '\n' }
+    }
+}
+
 #[derive(Clone, Debug)]
 /// Elements that shall be appended at the end of each command processing cycle
 pub enum AppendElement {
     Text(Rc<str>), // The specified text string
     Path(PathBuf), // The contents of the specified file path
+    Line(String),  // A line read from a file, without its separator
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
 /// A space mirroring IOChunk, but only with a String
 pub struct StringSpace {
-    pub content: String,   // Line content without newline
-    pub has_newline: bool, // True if \n-terminated
+    pub content: String,   // Line content without separator
+    pub has_newline: bool, // True if separator-terminated
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Types of address specifications that precede commands
 pub enum AddressType {
-    Re,      // Line that matches regex
-    Line,    // Specific line
-    RelLine, // Relative line
-    Last,    // Last line
+    Re,       // Line that matches regex
+    Line,     // Specific line
+    RelLine,  // Relative line
+    Step,     // Every step lines from first (GNU)
+    Multiple, // Range end at next line multiple (GNU)
+    Last,     // Last line
 }
 
 #[derive(Debug)]
 /// Format of an address
 pub struct Address {
-    pub atype: AddressType,  // Address type
-    pub value: AddressValue, // Line number or regex
+    pub atype: AddressType,     // Address type
+    pub value: AddressValue,    // Line number or regex
+    pub source: Option<String>, // RE address in canonical /re/ form (--debug)
 }
 
 #[derive(Debug)]
 pub enum AddressValue {
     LineNumber(usize),
+    Step(usize, usize), // First line and step
     Regex(Option<Regex>),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Case conversion of the replacement text that follows (GNU)
+pub enum CaseConversion {
+    Upper,     // \U: Convert to uppercase
+    Lower,     // \L: Convert to lowercase
+    UpperNext, // \u: Convert the next character to uppercase
+    LowerNext, // \l: Convert the next character to lowercase
+    End,       // \E: Stop converting
+}
+
 #[derive(Debug)]
 /// A single part of an RE replacement
 pub enum ReplacementPart {
-    Literal(String), // Normal text
-    WholeMatch,      // &
-    Group(u32),      // \1 to \9
+    Literal(String),      // Normal text
+    WholeMatch,           // &
+    Group(u32),           // \1 to \9
+    Case(CaseConversion), // \U, \L, \u, \l, \E
+}
+
+#[derive(Default)]
+/// Replacement text built by applying any specified case conversions
+struct CaseConvertedString {
+    result: String,
+    ongoing: Option<CaseConversion>, // Upper or Lower
+    next: Option<CaseConversion>,    // UpperNext or LowerNext
+}
+
+impl CaseConvertedString {
+    /// Apply the specified conversion to the text that will follow.
+    fn set(&mut self, conversion: CaseConversion) {
+        match conversion {
+            CaseConversion::Upper | CaseConversion::Lower => self.ongoing = Some(conversion),
+            CaseConversion::UpperNext | CaseConversion::LowerNext => self.next = Some(conversion),
+            CaseConversion::End => {
+                self.ongoing = None;
+                self.next = None;
+            }
+        }
+    }
+
+    /// Append the string, converting its case as specified.
+    fn push_str(&mut self, s: &str) {
+        if self.ongoing.is_none() && self.next.is_none() {
+            self.result.push_str(s);
+            return;
+        }
+
+        let mut chars = s.chars();
+        if let Some(next) = self.next
+            && let Some(first) = chars.next()
+        {
+            match next {
+                CaseConversion::UpperNext => self.result.extend(first.to_uppercase()),
+                _ => self.result.extend(first.to_lowercase()),
+            }
+            self.next = None;
+        }
+        match self.ongoing {
+            Some(CaseConversion::Upper) => {
+                chars.for_each(|c| self.result.extend(c.to_uppercase()));
+            }
+            Some(CaseConversion::Lower) => {
+                chars.for_each(|c| self.result.extend(c.to_lowercase()));
+            }
+            _ => self.result.push_str(chars.as_str()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -152,7 +229,7 @@ impl ReplacementTemplate {
     ///    template.apply_captures(&command, caps) });
     /// Returns an error if a backreference in the template was not matched by the RE.
     pub fn apply_captures(&self, command: &Command, caps: &Captures) -> UResult<String> {
-        let mut result = String::new();
+        let mut result = CaseConvertedString::default();
 
         // Invalid group numbers may end here through (unkown at compile time)
         // reused REs.
@@ -178,15 +255,17 @@ impl ReplacementTemplate {
                     let i: usize = (*n).try_into().unwrap();
                     result.push_str(caps.get(i)?.map(|m| m.as_str()).unwrap_or(""));
                 }
+
+                ReplacementPart::Case(conversion) => result.set(*conversion),
             }
         }
 
-        Ok(result)
+        Ok(result.result)
     }
 
     /// Apply the template to the given RE single match.
     pub fn apply_match(&self, m: &Match) -> String {
-        let mut result = String::new();
+        let mut result = CaseConvertedString::default();
 
         for part in &self.parts {
             match part {
@@ -197,9 +276,11 @@ impl ReplacementTemplate {
                 ReplacementPart::Group(_) => {
                     panic!("unexpected Regex group replacement")
                 }
+
+                ReplacementPart::Case(conversion) => result.set(*conversion),
             }
         }
-        result
+        result.result
     }
 }
 
@@ -207,10 +288,14 @@ impl ReplacementTemplate {
 /// Substitution command
 pub struct Substitution {
     pub regex: Option<Regex>,                         // Regular expression
+    pub pattern: String,                              // RE as specified (for --debug)
     pub replacement: ReplacementTemplate,             // Specified broken-down replacement
     pub occurrence: usize,                            // Which occurrence to substitute
+    pub global: bool,                                 // True if also the following ones (GNU Ng)
     pub print_flag: bool,                             // True if 'p' flag
     pub ignore_case: bool,                            // True if 'I' flag
+    pub multiline: bool,                              // True if 'M' flag (GNU)
+    pub execute: bool,                                // True if 'e' flag (GNU)
     pub write_file: Option<Rc<RefCell<NamedWriter>>>, // Writer to file if 'w' flag is used
 }
 
@@ -260,6 +345,23 @@ impl Transliteration {
         }
     }
 
+    /// Return the non-identity mappings in character order.
+    pub fn mappings(&self) -> Vec<(char, char)> {
+        let mut result: Vec<(char, char)> = self
+            .fast
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &to)| {
+                char::from_u32(i as u32)
+                    .filter(|&from| from != to)
+                    .map(|from| (from, to))
+            })
+            .chain(self.slow.iter().map(|(&from, &to)| (from, to)))
+            .collect();
+        result.sort_unstable();
+        result
+    }
+
     /// Look up a character transliteration.
     pub fn lookup(&self, ch: char) -> char {
         let cp = ch as usize;
@@ -307,6 +409,34 @@ impl Command {
             ..Default::default()
         }
     }
+
+    /// Return true if executing the command requires knowing whether
+    /// the current input line is the last one.
+    pub fn needs_last_line(&self) -> bool {
+        let is_last =
+            |addr: &Option<Address>| matches!(addr, Some(a) if a.atype == AddressType::Last);
+        // A c command applied to a range outputs its text at the end of
+        // input, if the range is not closed before it.
+        is_last(&self.addr1) || is_last(&self.addr2) || (self.code == 'c' && self.addr2.is_some())
+    }
+
+    /// Set the command's address range to its state before any input.
+    /// A 0,/re/ range is active, so that it can end on the first line.
+    pub fn reset_range(&mut self) {
+        let zero_start = matches!(
+            self.addr1,
+            Some(Address {
+                atype: AddressType::Line,
+                value: AddressValue::LineNumber(0),
+                ..
+            })
+        );
+        self.start_line = if zero_start && self.addr2.is_some() {
+            Some(0)
+        } else {
+            None
+        };
+    }
 }
 
 #[derive(Debug)]
@@ -317,7 +447,9 @@ pub enum CommandData {
     BranchTarget(Option<Rc<RefCell<Command>>>), // Commands for 'b', 't', '{'
     Label(Option<String>),                      // Label name for 'b', 't', ':'
     Path(PathBuf),                              // File path for 'r'
+    NamedReader(Rc<RefCell<NamedReader>>),      // File input for 'R' (GNU)
     NamedWriter(Rc<RefCell<NamedWriter>>),      // File output for 'w'
+    ShellCommand(Option<String>),               // Command for 'e' (GNU)
     Number(usize),                              // Number for 'l', 'q', 'Q' (GNU)
     Substitution(Box<Substitution>),            // Substitute command 's'
     Text(Rc<str>),                              // Text for 'a', 'c', 'i'
@@ -425,6 +557,58 @@ mod tests {
         assert_eq!(result, "key: x, value: 123");
     }
 
+    #[test]
+    // s/(.)(\w+) (\w+)/\u\1\L\2 \U\3\E!/
+    fn test_case_conversion() {
+        let template = ReplacementTemplate::new(vec![
+            ReplacementPart::Case(CaseConversion::UpperNext),
+            ReplacementPart::Group(1),
+            ReplacementPart::Case(CaseConversion::Lower),
+            ReplacementPart::Group(2),
+            ReplacementPart::Literal(" ".into()),
+            ReplacementPart::Case(CaseConversion::Upper),
+            ReplacementPart::Group(3),
+            ReplacementPart::Case(CaseConversion::End),
+            ReplacementPart::Literal("!".into()),
+        ]);
+        let input = &mut IOChunk::new_from_str("sTRASSE straße");
+        let caps = caps_for(r"(.)(\w+) (\w+)", input);
+        let cmd = Command::default();
+
+        let result = template.apply_captures(&cmd, &caps).unwrap();
+        assert_eq!(result, "Strasse STRASSE!");
+    }
+
+    #[test]
+    // s/abc/\u\L&/
+    fn test_case_conversion_next_and_rest() {
+        let template = ReplacementTemplate::new(vec![
+            ReplacementPart::Case(CaseConversion::UpperNext),
+            ReplacementPart::Case(CaseConversion::Lower),
+            ReplacementPart::WholeMatch,
+        ]);
+        let input = &mut IOChunk::new_from_str("éCOLE");
+        let m = Regex::new(".*").unwrap().find(input).unwrap().unwrap();
+
+        assert_eq!(template.apply_match(&m), "École");
+    }
+
+    #[test]
+    // s/(x*)a/\u\1b/
+    fn test_case_conversion_next_skips_empty() {
+        let template = ReplacementTemplate::new(vec![
+            ReplacementPart::Case(CaseConversion::UpperNext),
+            ReplacementPart::Group(1),
+            ReplacementPart::Literal("b".into()),
+        ]);
+        let input = &mut IOChunk::new_from_str("a");
+        let caps = caps_for(r"(x*)a", input);
+        let cmd = Command::default();
+
+        let result = template.apply_captures(&cmd, &caps).unwrap();
+        assert_eq!(result, "B");
+    }
+
     #[test]
     // s/(\w+):(\d+)/key: \1, value: \3/
     fn test_invalid_group() {
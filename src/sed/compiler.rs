@@ -9,25 +9,30 @@
 // file that was distributed with this source code.
 
 use crate::sed::command::{
-    Address, AddressType, AddressValue, Command, CommandData, ProcessingContext, ReplacementPart,
-    ReplacementTemplate, Substitution, Transliteration,
+    Address, AddressType, AddressValue, CaseConversion, Command, CommandData, ProcessingContext,
+    ReplacementPart, ReplacementTemplate, Substitution, Transliteration,
 };
-use crate::sed::delimited_parser::{parse_char_escape, parse_regex, parse_transliteration};
-use crate::sed::error_handling::{ScriptLocation, compilation_error, semantic_error};
+use crate::sed::delimited_parser::{
+    parse_char_escape, parse_regex, parse_script_escape, parse_transliteration,
+};
+use crate::sed::error_handling::{ScriptLocation, compilation_error, posix_check, semantic_error};
 use crate::sed::fast_regex::Regex;
+use crate::sed::named_reader::NamedReader;
 use crate::sed::named_writer::NamedWriter;
 use crate::sed::script_char_provider::ScriptCharProvider;
 use crate::sed::script_line_provider::{ScriptLineProvider, ScriptValue};
 
 use std::cell::RefCell;
+use std::iter::Peekable;
 use std::mem;
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::str::Chars;
 
-use terminal_size::{Width, terminal_size};
 use uucore::error::{UResult, USimpleError};
 
-const DEFAULT_OUTPUT_WIDTH: usize = 60;
+/// The GNU sed version whose scripts the v command accepts
+const GNU_VERSION: &str = "4.9";
 
 // Handling required after processing a command
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -182,8 +187,8 @@ fn resolve_branch_targets(
             resolve_branch_targets(Some(sub_head.clone()), context)?;
         }
 
-        // Only for 't' or 'b' commands:
-        if matches!(cmd.code, 't' | 'b') {
+        // Only for 't', 'T', or 'b' commands:
+        if matches!(cmd.code, 't' | 'T' | 'b') {
             // Take ownership of the current data
             let old_data = mem::replace(&mut cmd.data, CommandData::None);
 
@@ -278,6 +283,9 @@ fn compile_sequence(
             CommandHandling::Return => return Ok(head),
             CommandHandling::Continue => (),
         }
+        if cmd_mut.needs_last_line() {
+            context.needs_last_line = true;
+        }
         drop(cmd_mut);
 
         if let Some(ref t) = tail {
@@ -308,11 +316,8 @@ fn compile_address_range(
     let mut cmd = cmd.borrow_mut();
 
     line.eat_spaces();
-    if !line.eol()
-        && is_address_char(line.current())
-        && let Ok(addr1) = compile_address(lines, line, context)
-    {
-        cmd.addr1 = Some(addr1);
+    if !line.eol() && is_address_char(line.current()) {
+        cmd.addr1 = Some(compile_address(lines, line, context)?);
         n_addr += 1;
     }
 
@@ -320,17 +325,53 @@ fn compile_address_range(
     if n_addr == 1 && !line.eol() && line.current() == ',' {
         line.advance();
         line.eat_spaces();
-        if !line.eol()
-            && let Ok(addr2) = compile_address(lines, line, context)
-        {
-            cmd.addr2 = Some(addr2);
+        if !line.eol() && line.current() == '~' {
+            posix_check(lines, line, context, "a ~N address")?;
+            line.advance();
+            let number = parse_number(lines, line, true)?.unwrap();
+            cmd.addr2 = Some(Address {
+                atype: AddressType::Multiple,
+                value: AddressValue::LineNumber(number),
+                source: None,
+            });
+            n_addr += 1;
+        } else if !line.eol() {
+            cmd.addr2 = Some(compile_address(lines, line, context)?);
             n_addr += 1;
         }
     }
 
+    // Line 0 can only start a range ending with an RE: 0,/re/
+    if let Some(addr1) = &cmd.addr1
+        && addr1.atype == AddressType::Line
+        && matches!(addr1.value, AddressValue::LineNumber(0))
+    {
+        match &cmd.addr2 {
+            Some(addr2) if addr2.atype == AddressType::Re => {
+                posix_check(lines, line, context, "the 0,/re/ address")?;
+                cmd.reset_range();
+            }
+            _ => return compilation_error(lines, line, "invalid usage of line address 0"),
+        }
+    }
+
     Ok(n_addr)
 }
 
+/// Fail with an error located at the current command or flag,
+/// if access to files and programs is disabled through --sandbox.
+fn check_sandbox(
+    lines: &ScriptLineProvider,
+    line: &ScriptCharProvider,
+    context: &ProcessingContext,
+) -> UResult<()> {
+    if context.sandbox {
+        compilation_error(lines, line, "e/r/w commands disabled in sandbox mode")
+    } else {
+        Ok(())
+    }
+}
+
 /// Read the line's remaining characters as a file path and return it.
 fn read_file_path(lines: &ScriptLineProvider, line: &mut ScriptCharProvider) -> UResult<PathBuf> {
     line.advance(); // Skip the command/w character
@@ -356,6 +397,7 @@ fn compile_address(
     context: &ProcessingContext,
 ) -> UResult<Address> {
     let mut icase = false;
+    let mut multiline = false;
 
     if line.eol() {
         return compilation_error(lines, line, "expected context address");
@@ -368,19 +410,37 @@ fn compile_address(
                 // The next character is an arbitrary delimiter
                 line.advance();
             }
-            let re = parse_regex(lines, line)?;
+            let re = parse_regex(lines, line, context)?;
             // Skip over delimiter
             line.advance();
 
-            line.eat_spaces();
-            if !line.eol() && line.current() == 'I' {
-                icase = true;
+            let mut modifiers = String::new();
+            loop {
+                line.eat_spaces();
+                if line.eol() {
+                    break;
+                }
+                match line.current() {
+                    'I' => {
+                        posix_check(lines, line, context, "the I modifier")?;
+                        icase = true;
+                    }
+                    'M' => {
+                        posix_check(lines, line, context, "the M modifier")?;
+                        multiline = true;
+                    }
+                    _ => break,
+                }
+                modifiers.push(line.current());
                 line.advance();
             }
 
             Ok(Address {
                 atype: AddressType::Re,
-                value: AddressValue::Regex(compile_regex(lines, line, &re, context, icase)?),
+                value: AddressValue::Regex(compile_regex(
+                    lines, line, &re, context, icase, multiline,
+                )?),
+                source: Some(format!("/{}/{modifiers}", escape_delimiter(&re, '/'))),
             })
         }
         '$' => {
@@ -388,27 +448,61 @@ fn compile_address(
             Ok(Address {
                 atype: AddressType::Last,
                 value: AddressValue::LineNumber(0),
+                source: None,
             })
         }
         '+' => {
+            posix_check(lines, line, context, "a +N address")?;
             line.advance();
             let number = parse_number(lines, line, true)?.unwrap();
             Ok(Address {
                 atype: AddressType::RelLine,
                 value: AddressValue::LineNumber(number),
+                source: None,
             })
         }
         c if c.is_ascii_digit() => {
             let number = parse_number(lines, line, true)?.unwrap();
+            if !line.eol() && line.current() == '~' {
+                posix_check(lines, line, context, "a first~step address")?;
+                line.advance();
+                let step = parse_number(lines, line, true)?.unwrap();
+                // As in GNU sed, a zero step matches only the first line.
+                if step > 0 {
+                    return Ok(Address {
+                        atype: AddressType::Step,
+                        value: AddressValue::Step(number, step),
+                        source: None,
+                    });
+                }
+            }
             Ok(Address {
                 atype: AddressType::Line,
                 value: AddressValue::LineNumber(number),
+                source: None,
             })
         }
-        _ => panic!("invalid context address"),
+        _ => compilation_error(lines, line, "expected context address"),
     }
 }
 
+/// Return the specified delimited string (RE or replacement) in a form
+/// that can appear in a script between the specified delimiters.
+pub fn escape_delimiter(s: &str, delimiter: char) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => result.push_str("\\n"),
+            c if c == delimiter => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
 /// Parse and return the decimal number at the current line position.
 /// Advance the line to first non-digit or EOL.
 /// Issue an error if the number is required.
@@ -461,28 +555,64 @@ fn parse_command_ending(
     Ok(())
 }
 
+/// Return true if the BRE characters that follow end an expression,
+/// a group, or (unless posix is set) an alternative.
+fn at_subexpression_end(chars: &Peekable<Chars>, posix: bool) -> bool {
+    let mut ahead = chars.clone();
+    match (ahead.next(), ahead.next()) {
+        (None, _) => true,
+        (Some('\\'), Some(')')) => true,
+        (Some('\\'), Some('|')) => !posix,
+        _ => false,
+    }
+}
+
 /// Convert a primitive BRE pattern to a safe ERE-compatible pattern string.
 /// - Replaces `\(` and `\)` with `(` and `)`.
+/// - Replaces the interval delimiters `\{` and `\}` with `{` and `}`.
 /// - Puts single-digit back-references in non-capturing groups..
+/// - Unless posix is set, replaces the GNU `\\+`, `\\?`, and `\\|` operators
+///   with `+`, `?`, and `|`.
 /// - Escapes ERE-only metacharacters: `+ ? { } | ( )`.
-/// - Leaves all other characters as-is.
-fn bre_to_ere(pattern: &str) -> String {
+/// - Leaves bracket expressions and all other characters as-is.
+fn bre_to_ere(pattern: &str, posix: bool) -> String {
     let mut result = String::with_capacity(pattern.len());
     let mut chars = pattern.chars().peekable();
 
     let mut at_beginning = true;
-    let mut previous: Option<char> = None;
     while let Some(c) = chars.next() {
-        if c == '\\' {
+        // True after the start of a group or an alternative
+        let mut at_subexpression = false;
+        if c == '[' {
+            result.push_str(&scan_bracket(&mut chars));
+        } else if c == '\\' {
             match chars.peek() {
                 Some('(') => {
                     chars.next();
                     result.push('('); // Group start
+                    at_subexpression = true;
+                }
+                Some(&op @ ('+' | '?' | '|')) if !posix => {
+                    chars.next();
+                    result.push(op); // GNU repetition or alternation
+                    at_subexpression = op == '|';
                 }
                 Some(')') => {
                     chars.next();
                     result.push(')'); // Group end
                 }
+                Some('{') => {
+                    chars.next();
+                    result.push('{'); // Interval start
+                    if chars.peek() == Some(&',') {
+                        // Make the omitted minimum of \{,m\} explicit.
+                        result.push('0');
+                    }
+                }
+                Some('}') => {
+                    chars.next();
+                    result.push('}'); // Interval end
+                }
                 Some(v) if v.is_ascii_digit() => {
                     // Back-reference.  In sed BREs these are single-digit
                     // (\1-\9) whereas fancy_regex supports multi-digit
@@ -511,9 +641,9 @@ fn bre_to_ere(pattern: &str) -> String {
                     result.push('\\');
                     result.push(c);
                 }
-                '^' if !at_beginning && previous != Some('[') => {
+                '^' if !at_beginning => {
                     // In BREs ^ has special meaning at the beginning
-                    // and as bracket negation.  This heuristic escapes
+                    // of an expression or subexpression.  This heuristic escapes
                     // all other uses, which per POSIX are valid in EREs.
                     // "the ERE "a^b" is valid, but can never match because
                     // the 'a' prevents the expression "^b" from matching
@@ -522,7 +652,7 @@ fn bre_to_ere(pattern: &str) -> String {
                     result.push('\\');
                     result.push(c);
                 }
-                '$' if chars.peek().is_some() => {
+                '$' if !at_subexpression_end(&chars, posix) => {
                     // Similarly for $ appearing not at the end.
                     result.push('\\');
                     result.push(c);
@@ -530,13 +660,239 @@ fn bre_to_ere(pattern: &str) -> String {
                 _ => result.push(c),
             }
         }
-        at_beginning = false;
-        previous = Some(c);
+        at_beginning = at_subexpression;
     }
 
     result
 }
 
+/// Return the text of the bracket expression whose opening `[` has just
+/// been read from chars, up to and including its closing `]`.
+/// Backslashes within it are literal characters.
+/// An unterminated expression is returned as is.
+fn scan_bracket(chars: &mut Peekable<Chars>) -> String {
+    let mut result = String::from("[");
+
+    if let Some(c) = chars.next_if_eq(&'^') {
+        result.push(c);
+    }
+    if let Some(c) = chars.next_if_eq(&']') {
+        result.push(c);
+    }
+
+    while let Some(c) = chars.next() {
+        result.push(c);
+        match c {
+            ']' => break,
+            '[' => {
+                // Class, equivalence class, or collating symbol
+                if let Some(marker) = chars.next_if(|c| matches!(c, ':' | '=' | '.')) {
+                    result.push(marker);
+                    let mut previous = None;
+                    for c in chars.by_ref() {
+                        result.push(c);
+                        if previous == Some(marker) && c == ']' {
+                            break;
+                        }
+                        previous = Some(c);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    result
+}
+
+/// An element of a bracket expression
+enum BracketElement {
+    Char(char),          // A literal character
+    Class(&'static str), // A character class in engine syntax
+}
+
+/// Return the engine equivalent of the named POSIX character class.
+/// Apart from digit and xdigit, classes follow their Unicode meaning.
+fn posix_class(name: &str) -> Option<&'static str> {
+    match name {
+        "alnum" => Some(r"\p{Alphabetic}0-9"),
+        "alpha" => Some(r"\p{Alphabetic}"),
+        "blank" => Some(r"\p{Zs}\t"),
+        "cntrl" => Some(r"\p{Cc}"),
+        "digit" => Some("0-9"),
+        "graph" => Some(r"[^\p{C}\p{Z}]"),
+        "lower" => Some(r"\p{Lowercase}"),
+        "print" => Some(r"[^\p{C}]"),
+        "punct" => Some(r"\p{P}\p{S}"),
+        "space" => Some(r"\s"),
+        "upper" => Some(r"\p{Uppercase}"),
+        "xdigit" => Some("0-9A-Fa-f"),
+        _ => None,
+    }
+}
+
+/// Append to result the bracket expression element in engine syntax,
+/// escaping characters that have a special meaning in its brackets.
+fn push_bracket_element(result: &mut String, element: &BracketElement) {
+    match *element {
+        BracketElement::Char(c) => {
+            if matches!(c, '\\' | '[' | ']' | '-' | '^' | '&' | '~') {
+                result.push('\\');
+            }
+            result.push(c);
+        }
+        BracketElement::Class(class) => result.push_str(class),
+    }
+}
+
+/// Parse and return the bracket expression element starting with c.
+fn parse_bracket_element(
+    lines: &ScriptLineProvider,
+    line: &ScriptCharProvider,
+    c: char,
+    chars: &mut Peekable<Chars>,
+) -> UResult<BracketElement> {
+    let marker = match c {
+        '[' => chars.next_if(|c| matches!(c, ':' | '=' | '.')),
+        _ => None,
+    };
+    let Some(marker) = marker else {
+        return Ok(BracketElement::Char(c));
+    };
+
+    let mut name = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == marker && chars.next_if_eq(&']').is_some() => break,
+            Some(c) => name.push(c),
+            None => return compilation_error(lines, line, "Unmatched [, [^, [:, [., or [="),
+        }
+    }
+
+    if marker == ':' {
+        return match posix_class(&name) {
+            Some(class) => Ok(BracketElement::Class(class)),
+            None => compilation_error(lines, line, "Invalid character class name"),
+        };
+    }
+
+    // Equivalence classes and collating symbols are supported only for
+    // single characters, which stand for themselves.
+    let mut name_chars = name.chars();
+    match (name_chars.next(), name_chars.next()) {
+        (Some(c), None) => Ok(BracketElement::Char(c)),
+        _ => compilation_error(lines, line, "Invalid collation character"),
+    }
+}
+
+/// Translate into engine syntax the bracket expression whose opening `[`
+/// has just been read from chars.
+/// - Maps POSIX character classes to their engine equivalents.
+/// - Replaces equivalence classes and collating symbols with the
+///   character they name.
+/// - Escapes characters, such as a leading `]` or an edge `-`, that are
+///   literal in POSIX but special to the engine.
+/// - In multiline mode, keeps negated expressions from matching newlines.
+fn translate_bracket(
+    lines: &ScriptLineProvider,
+    line: &ScriptCharProvider,
+    chars: &mut Peekable<Chars>,
+    multiline: bool,
+) -> UResult<String> {
+    let mut result = String::from("[");
+
+    if let Some(c) = chars.next_if_eq(&'^') {
+        result.push(c);
+        if multiline {
+            result.push('\n');
+        }
+    }
+
+    let mut first = true;
+    loop {
+        let c = match chars.next() {
+            Some(']') if !first => break,
+            Some(c) => c,
+            None => return compilation_error(lines, line, "Unmatched [, [^, [:, [., or [="),
+        };
+        first = false;
+
+        let start = parse_bracket_element(lines, line, c, chars)?;
+
+        // A - that is not the last element denotes a range.
+        let mut ahead = chars.clone();
+        let end = if ahead.next() == Some('-') && !matches!(ahead.next(), Some(']') | None) {
+            chars.next();
+            let c = chars.next().unwrap();
+            Some(parse_bracket_element(lines, line, c, chars)?)
+        } else {
+            None
+        };
+
+        push_bracket_element(&mut result, &start);
+        if let Some(end) = end {
+            match (start, &end) {
+                (BracketElement::Class(_), _) | (_, BracketElement::Class(_)) => {
+                    return compilation_error(lines, line, "Invalid range end");
+                }
+                (BracketElement::Char(from), BracketElement::Char(to)) if from > *to => {
+                    return compilation_error(lines, line, "Invalid range end");
+                }
+                _ => (),
+            }
+            result.push('-');
+            push_bracket_element(&mut result, &end);
+        }
+    }
+
+    result.push(']');
+    Ok(result)
+}
+
+/// Translate POSIX and GNU RE constructs into their engine equivalents.
+/// - Translates bracket expressions through [`translate_bracket`].
+/// - Replaces `` \` `` and `\'` with `\A` and `\z`, which match only at
+///   the beginning and end of the buffer, even in multiline mode.
+/// - Replaces the word start and end anchors `\<` and `\>` with
+///   `\b{start}` and `\b{end}`.
+/// - Keeps `\b`, `\B`, `\w`, `\W`, `\s`, and `\S`, which the engine
+///   supports with the same meaning.
+/// - If posix is set, makes all the above GNU escapes match literally.
+/// - Leaves all other characters and escapes as-is.
+fn translate_to_engine(
+    lines: &ScriptLineProvider,
+    line: &ScriptCharProvider,
+    pattern: &str,
+    posix: bool,
+    multiline: bool,
+) -> UResult<String> {
+    let mut result = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => result.push_str(&translate_bracket(lines, line, &mut chars, multiline)?),
+            '\\' => match chars.next() {
+                Some(c @ ('`' | '\'' | '<' | '>' | 'b' | 'B' | 'w' | 'W' | 's' | 'S')) if posix => {
+                    result.push(c);
+                }
+                Some('`') => result.push_str(r"\A"),
+                Some('\'') => result.push_str(r"\z"),
+                Some('<') => result.push_str(r"\b{start}"),
+                Some('>') => result.push_str(r"\b{end}"),
+                Some(next) => {
+                    result.push('\\');
+                    result.push(next);
+                }
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+
+    Ok(result)
+}
+
 /// Compile the provided regular expression string into a corresponding engine.
 /// An empty pattern results in None, which means that the last RE employed
 /// at runtime will be used.
@@ -546,6 +902,7 @@ fn compile_regex(
     pattern: &str,
     context: &ProcessingContext,
     icase: bool,
+    multiline: bool,
 ) -> UResult<Option<Regex>> {
     if pattern.is_empty() {
         return Ok(None);
@@ -555,9 +912,12 @@ fn compile_regex(
     let pattern = if context.regex_extended {
         pattern
     } else {
-        &bre_to_ere(pattern)
+        &bre_to_ere(pattern, context.posix)
     };
 
+    // Map POSIX brackets and GNU-specific escapes to the engine's syntax.
+    let pattern = &translate_to_engine(lines, line, pattern, context.posix, multiline)?;
+
     // Add case-insensitive modifier if needed.
     let pattern = if icase {
         format!("(?i){pattern}")
@@ -566,7 +926,7 @@ fn compile_regex(
     };
 
     // Compile into engine.
-    let compiled = Regex::new(&pattern).map_err(|e| {
+    let compiled = Regex::with_multiline(&pattern, multiline).map_err(|e| {
         compilation_error::<Regex>(lines, line, format!("invalid regex '{pattern}': {e}"))
             .unwrap_err()
     })?;
@@ -574,10 +934,22 @@ fn compile_regex(
     Ok(Some(compiled))
 }
 
+/// Return the case conversion specified by the replacement escape character.
+fn case_conversion(c: char) -> CaseConversion {
+    match c {
+        'U' => CaseConversion::Upper,
+        'L' => CaseConversion::Lower,
+        'u' => CaseConversion::UpperNext,
+        'l' => CaseConversion::LowerNext,
+        _ => CaseConversion::End,
+    }
+}
+
 /// Compile a regular expression replacement string.
 pub fn compile_replacement(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
+    context: &ProcessingContext,
 ) -> UResult<ReplacementTemplate> {
     let mut parts = Vec::new();
     let mut literal = String::new();
@@ -609,6 +981,9 @@ pub fn compile_replacement(
                     match line.current() {
                         // \0 - \9
                         c @ '0'..='9' => {
+                            if c == '0' {
+                                posix_check(lines, line, context, "the \\0 reference")?;
+                            }
                             let ref_num = c.to_digit(10).unwrap();
 
                             if !literal.is_empty() {
@@ -622,6 +997,16 @@ pub fn compile_replacement(
                             line.advance();
                         }
 
+                        // Case conversion \L, \l, \U, \u, \E (GNU)
+                        c @ ('L' | 'l' | 'U' | 'u' | 'E') => {
+                            posix_check(lines, line, context, format!("the \\{c} escape"))?;
+                            if !literal.is_empty() {
+                                parts.push(ReplacementPart::Literal(std::mem::take(&mut literal)));
+                            }
+                            parts.push(ReplacementPart::Case(case_conversion(c)));
+                            line.advance();
+                        }
+
                         // Literal \ and &
                         '\\' | '&' => {
                             literal.push(line.current());
@@ -635,7 +1020,7 @@ pub fn compile_replacement(
                         }
 
                         // other escape sequences
-                        _ => match parse_char_escape(line) {
+                        _ => match parse_script_escape(lines, line, context)? {
                             Some(decoded) => literal.push(decoded),
                             None => {
                                 literal.push('\\');
@@ -704,14 +1089,17 @@ fn compile_subst_command(
         );
     }
 
-    let pattern = parse_regex(lines, line)?;
+    let pattern = parse_regex(lines, line, context)?;
 
-    let mut subst = Box::new(Substitution::default());
+    let mut subst = Box::new(Substitution {
+        pattern: pattern.clone(),
+        ..Default::default()
+    });
 
-    subst.replacement = compile_replacement(lines, line)?;
-    compile_subst_flags(lines, line, &mut subst)?;
+    subst.replacement = compile_replacement(lines, line, context)?;
+    compile_subst_flags(lines, line, &mut subst, context)?;
 
-    if pattern.is_empty() && subst.ignore_case {
+    if pattern.is_empty() && (subst.ignore_case || subst.multiline) {
         return compilation_error(
             lines,
             line,
@@ -719,8 +1107,15 @@ fn compile_subst_command(
         );
     }
 
-    // Compile regex with now known ignore_case flag.
-    subst.regex = compile_regex(lines, line, &pattern, context, subst.ignore_case)?;
+    // Compile regex with now known ignore_case and multiline flags.
+    subst.regex = compile_regex(
+        lines,
+        line,
+        &pattern,
+        context,
+        subst.ignore_case,
+        subst.multiline,
+    )?;
 
     // Catch invalid group references at compile time, if possible.
     if let Some(regex) = &subst.regex
@@ -747,7 +1142,7 @@ fn compile_trans_command(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
     cmd: &mut Command,
-    _context: &mut ProcessingContext,
+    context: &mut ProcessingContext,
 ) -> UResult<CommandHandling> {
     line.advance(); // move past 'y'
 
@@ -760,8 +1155,8 @@ fn compile_trans_command(
         );
     }
 
-    let source = parse_transliteration(lines, line)?;
-    let target = parse_transliteration(lines, line)?;
+    let source = parse_transliteration(lines, line, context)?;
+    let target = parse_transliteration(lines, line, context)?;
     if source.chars().count() != target.chars().count() {
         return compilation_error(
             lines,
@@ -783,12 +1178,17 @@ pub fn compile_subst_flags(
     lines: &ScriptLineProvider,
     line: &mut ScriptCharProvider,
     subst: &mut Substitution,
+    context: &ProcessingContext,
 ) -> UResult<()> {
-    let mut seen_g_or_n = false;
+    let mut seen_g = false;
+    let mut seen_n = false;
 
     subst.occurrence = 1; // default
+    subst.global = false;
     subst.print_flag = false;
     subst.ignore_case = false;
+    subst.multiline = false;
+    subst.execute = false;
     subst.write_file = None;
 
     loop {
@@ -799,15 +1199,24 @@ pub fn compile_subst_flags(
 
         match line.current() {
             'g' => {
-                if seen_g_or_n {
+                if seen_g {
                     return compilation_error(
                         lines,
                         line,
-                        "multiple 'g' or numeric flags in substitute command",
+                        "multiple 'g' flags in substitute command",
                     );
                 }
-                seen_g_or_n = true;
-                subst.occurrence = 0;
+                if seen_n {
+                    posix_check(lines, line, context, "combining the g and numeric flags")?;
+                }
+                seen_g = true;
+                if subst.occurrence == 1 {
+                    // All occurrences
+                    subst.occurrence = 0;
+                } else {
+                    // The specified occurrence and all following ones
+                    subst.global = true;
+                }
                 line.advance();
             }
 
@@ -817,18 +1226,28 @@ pub fn compile_subst_flags(
             }
 
             'i' | 'I' => {
+                posix_check(lines, line, context, "the I modifier")?;
                 subst.ignore_case = true;
                 line.advance();
             }
 
+            'm' | 'M' => {
+                posix_check(lines, line, context, "the M modifier")?;
+                subst.multiline = true;
+                line.advance();
+            }
+
             _c @ '1'..='9' => {
-                if seen_g_or_n {
+                if seen_n {
                     return compilation_error(
                         lines,
                         line,
-                        "multiple 'g' or numeric flags in substitute command",
+                        "multiple numeric flags in substitute command",
                     );
                 }
+                if seen_g {
+                    posix_check(lines, line, context, "combining the g and numeric flags")?;
+                }
 
                 let mut number = 0usize;
                 while !line.eol() && line.current().is_ascii_digit() {
@@ -846,14 +1265,28 @@ pub fn compile_subst_flags(
                     line.advance();
                 }
 
-                subst.occurrence = number;
-                seen_g_or_n = true;
+                if !seen_g {
+                    subst.occurrence = number;
+                } else if number > 1 {
+                    // The specified occurrence and all following ones
+                    subst.occurrence = number;
+                    subst.global = true;
+                }
+                seen_n = true;
+            }
+
+            'e' => {
+                check_sandbox(lines, line, context)?;
+                posix_check(lines, line, context, "the e flag")?;
+                subst.execute = true;
+                line.advance();
             }
 
             'w' => {
+                check_sandbox(lines, line, context)?;
                 let location = ScriptLocation::at_position(lines, line);
                 let path = read_file_path(lines, line)?;
-                subst.write_file = Some(NamedWriter::new(path, location)?);
+                subst.write_file = Some(NamedWriter::new(path, location, context)?);
                 return Ok(()); // 'w' is the last flag allowed
             }
 
@@ -911,8 +1344,11 @@ fn compile_empty_command(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
     cmd: &mut Command,
-    _context: &mut ProcessingContext,
+    context: &mut ProcessingContext,
 ) -> UResult<CommandHandling> {
+    if matches!(cmd.code, 'F' | 'z') {
+        posix_check(lines, line, context, format!("the {} command", cmd.code))?;
+    }
     line.advance(); // Skip the command character
     line.eat_spaces(); // Skip any trailing whitespace
 
@@ -925,23 +1361,69 @@ fn compile_read_file_command(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
     cmd: &mut Command,
-    _context: &mut ProcessingContext,
+    context: &mut ProcessingContext,
 ) -> UResult<CommandHandling> {
+    check_sandbox(lines, line, context)?;
     let path = read_file_path(lines, line)?;
     cmd.data = CommandData::Path(path);
     Ok(CommandHandling::Continue)
 }
 
-// Handles w
+// Handles R
+fn compile_read_line_command(
+    lines: &mut ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    cmd: &mut Command,
+    context: &mut ProcessingContext,
+) -> UResult<CommandHandling> {
+    check_sandbox(lines, line, context)?;
+    posix_check(lines, line, context, "the R command")?;
+    let location = ScriptLocation::at_position(lines, line);
+    let path = read_file_path(lines, line)?;
+    cmd.data = CommandData::NamedReader(NamedReader::new(path, location, context.separator()));
+    Ok(CommandHandling::Continue)
+}
+
+// Handles w and W
 fn compile_write_file_command(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
     cmd: &mut Command,
-    _context: &mut ProcessingContext,
+    context: &mut ProcessingContext,
 ) -> UResult<CommandHandling> {
+    check_sandbox(lines, line, context)?;
+    if cmd.code == 'W' {
+        posix_check(lines, line, context, "the W command")?;
+    }
     let location = ScriptLocation::at_position(lines, line);
     let path = read_file_path(lines, line)?;
-    cmd.data = CommandData::NamedWriter(NamedWriter::new(path, location)?);
+    cmd.data = CommandData::NamedWriter(NamedWriter::new(path, location, context)?);
+    Ok(CommandHandling::Continue)
+}
+
+// Handles e
+fn compile_exec_command(
+    lines: &mut ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    cmd: &mut Command,
+    context: &mut ProcessingContext,
+) -> UResult<CommandHandling> {
+    check_sandbox(lines, line, context)?;
+    posix_check(lines, line, context, "the e command")?;
+    line.advance(); // Skip the command character
+    line.eat_spaces(); // Skip any leading whitespace
+
+    let mut command = String::new();
+    while !line.eol() {
+        command.push(line.current());
+        line.advance();
+    }
+
+    if command.is_empty() {
+        cmd.data = CommandData::ShellCommand(None);
+    } else {
+        cmd.data = CommandData::ShellCommand(Some(command));
+    }
     Ok(CommandHandling::Continue)
 }
 
@@ -964,7 +1446,7 @@ fn compile_label_command(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
     cmd: &mut Command,
-    _context: &mut ProcessingContext,
+    context: &mut ProcessingContext,
 ) -> UResult<CommandHandling> {
     /// Return true if `c` is in the POSIX portable filename character set.
     fn is_portable_filename_char(c: char) -> bool {
@@ -972,6 +1454,9 @@ fn compile_label_command(
         || matches!(c, '.' | '_' | '-')
     }
 
+    if cmd.code == 'T' {
+        posix_check(lines, line, context, "the T command")?;
+    }
     line.advance(); // Skip the command character
     line.eat_spaces(); // Skip any leading whitespace
 
@@ -995,13 +1480,50 @@ fn compile_label_command(
     Ok(CommandHandling::Continue)
 }
 
-/// Return the width of the command's terminal or a default.
-fn output_width() -> usize {
-    if let Some((Width(w), _)) = terminal_size() {
-        w as usize
-    } else {
-        DEFAULT_OUTPUT_WIDTH
+/// Return the components of a dotted version number.
+fn parse_version(version: &str) -> Option<Vec<usize>> {
+    version.split('.').map(|n| n.parse().ok()).collect()
+}
+
+// Handles v
+fn compile_version_command(
+    lines: &mut ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    cmd: &mut Command,
+    context: &mut ProcessingContext,
+) -> UResult<CommandHandling> {
+    posix_check(lines, line, context, "the v command")?;
+    line.advance(); // Skip the command character
+    line.eat_spaces(); // Skip any leading whitespace
+
+    let mut version = String::new();
+    while !line.eol() && (line.current().is_ascii_digit() || line.current() == '.') {
+        version.push(line.current());
+        line.advance();
     }
+
+    if !version.is_empty() {
+        let Some(mut required) = parse_version(&version) else {
+            return compilation_error(lines, line, format!("invalid version `{version}'"));
+        };
+        let mut supported = parse_version(GNU_VERSION).unwrap();
+        let len = required.len().max(supported.len());
+        required.resize(len, 0);
+        supported.resize(len, 0);
+        if required > supported {
+            return compilation_error(
+                lines,
+                line,
+                format!(
+                    "expected newer version of sed (requested {version}, supported {GNU_VERSION})"
+                ),
+            );
+        }
+    }
+
+    line.eat_spaces(); // Skip any trailing whitespace
+    parse_command_ending(lines, line, cmd)?;
+    Ok(CommandHandling::Continue)
 }
 
 /// Compile commands that take a number as an argument.
@@ -1010,11 +1532,22 @@ fn compile_number_command(
     lines: &mut ScriptLineProvider,
     line: &mut ScriptCharProvider,
     cmd: &mut Command,
-    _context: &mut ProcessingContext,
+    context: &mut ProcessingContext,
 ) -> UResult<CommandHandling> {
+    if cmd.code == 'Q' {
+        posix_check(lines, line, context, "the Q command")?;
+    }
     line.advance(); // Skip the command character
     line.eat_spaces(); // Skip any leading whitespace
 
+    if !line.eol() && line.current().is_ascii_digit() {
+        posix_check(
+            lines,
+            line,
+            context,
+            format!("an argument to the {} command", cmd.code),
+        )?;
+    }
     match parse_number(lines, line, false)? {
         Some(n) => {
             cmd.data = CommandData::Number(n);
@@ -1024,7 +1557,8 @@ fn compile_number_command(
                 cmd.data = CommandData::Number(0);
             }
             'l' => {
-                cmd.data = CommandData::Number(output_width());
+                // Wrap as specified with -l (default 70).
+                cmd.data = CommandData::Number(context.length);
             }
             _ => panic!("invalid number-expecting command"),
         },
@@ -1228,7 +1762,8 @@ fn get_cmd_spec(
             n_addr: 1,
             handler: compile_text_command,
         }),
-        'b' | 't' => Ok(CommandSpec {
+        // T is a GNU extension
+        'b' | 't' | 'T' => Ok(CommandSpec {
             n_addr: 2,
             handler: compile_label_command,
         }),
@@ -1236,10 +1771,20 @@ fn get_cmd_spec(
             n_addr: 2,
             handler: compile_text_command,
         }),
+        // e is a GNU extension
+        'e' => Ok(CommandSpec {
+            n_addr: 2,
+            handler: compile_exec_command,
+        }),
         'd' | 'D' | 'g' | 'G' | 'h' | 'H' | 'n' | 'N' | 'p' | 'P' | 'x' => Ok(CommandSpec {
             n_addr: 2,
             handler: compile_empty_command,
         }),
+        // F and z are GNU extensions
+        'F' | 'z' => Ok(CommandSpec {
+            n_addr: 2,
+            handler: compile_empty_command,
+        }),
         'l' => Ok(CommandSpec {
             n_addr: 2,
             handler: compile_number_command,
@@ -1253,14 +1798,25 @@ fn get_cmd_spec(
             n_addr: 1,
             handler: compile_read_file_command,
         }),
+        // R is a GNU extension
+        'R' => Ok(CommandSpec {
+            n_addr: 2,
+            handler: compile_read_line_command,
+        }),
         's' => Ok(CommandSpec {
             n_addr: 2,
             handler: compile_subst_command,
         }),
-        'w' => Ok(CommandSpec {
+        // W is a GNU extension
+        'w' | 'W' => Ok(CommandSpec {
             n_addr: 2,
             handler: compile_write_file_command,
         }),
+        // v is a GNU extension
+        'v' => Ok(CommandSpec {
+            n_addr: 0,
+            handler: compile_version_command,
+        }),
         'y' => Ok(CommandSpec {
             n_addr: 2,
             handler: compile_trans_command,
@@ -1460,7 +2016,7 @@ mod tests {
     #[test]
     fn test_compile_re_basic() {
         let (lines, chars) = dummy_providers();
-        let regex = compile_regex(&lines, &chars, "abc", &ctx(), false)
+        let regex = compile_regex(&lines, &chars, "abc", &ctx(), false, false)
             .unwrap()
             .expect("regex should be present");
         assert!(regex.is_match(&mut IOChunk::new_from_str("abc")).unwrap());
@@ -1470,7 +2026,7 @@ mod tests {
     #[test]
     fn test_compile_re_case_insensitive() {
         let (lines, chars) = dummy_providers();
-        let regex = compile_regex(&lines, &chars, "abc", &ctx(), true)
+        let regex = compile_regex(&lines, &chars, "abc", &ctx(), true, false)
             .unwrap()
             .expect("regex should be present");
         assert!(regex.is_match(&mut IOChunk::new_from_str("abc")).unwrap());
@@ -1478,10 +2034,41 @@ mod tests {
         assert!(regex.is_match(&mut IOChunk::new_from_str("AbC")).unwrap());
     }
 
+    #[test]
+    fn test_compile_re_gnu_operators() {
+        let (lines, chars) = dummy_providers();
+        let regex = compile_regex(&lines, &chars, r"ab\+c\?\|x", &ctx(), false, false)
+            .unwrap()
+            .expect("regex should be present");
+        assert!(matches!(regex, Regex::Byte(_)));
+        assert!(regex.is_match(&mut IOChunk::new_from_str("abbb")).unwrap());
+        assert!(regex.is_match(&mut IOChunk::new_from_str("x")).unwrap());
+        assert!(!regex.is_match(&mut IOChunk::new_from_str("a+c")).unwrap());
+    }
+
+    #[test]
+    fn test_compile_re_gnu_operators_posix() {
+        let (lines, chars) = dummy_providers();
+        let context = ProcessingContext {
+            posix: true,
+            ..Default::default()
+        };
+        let regex = compile_regex(&lines, &chars, r"ab\+c\?\|x", &context, false, false)
+            .unwrap()
+            .expect("regex should be present");
+        assert!(matches!(regex, Regex::Literal(_)));
+        assert!(
+            regex
+                .is_match(&mut IOChunk::new_from_str("ab+c?|x"))
+                .unwrap()
+        );
+        assert!(!regex.is_match(&mut IOChunk::new_from_str("abbb")).unwrap());
+    }
+
     #[test]
     fn test_compile_re_invalid() {
         let (lines, chars) = dummy_providers();
-        let result = compile_regex(&lines, &chars, "a[d", &ctx(), false);
+        let result = compile_regex(&lines, &chars, "a[d", &ctx(), false, false);
         assert!(result.is_err()); // Should fail due to open bracketed expression
     }
 
@@ -1510,6 +2097,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compile_addr_step() {
+        let (lines, mut chars) = make_providers("0~4");
+        let addr = compile_address(&lines, &mut chars, &ctx()).unwrap();
+        assert!(matches!(addr.atype, AddressType::Step));
+        assert!(matches!(addr.value, AddressValue::Step(0, 4)));
+    }
+
+    #[test]
+    fn test_compile_addr_step_zero() {
+        let (lines, mut chars) = make_providers("3~0");
+        let addr = compile_address(&lines, &mut chars, &ctx()).unwrap();
+        assert!(matches!(addr.atype, AddressType::Line));
+        assert!(matches!(addr.value, AddressValue::LineNumber(3)));
+    }
+
+    #[test]
+    fn test_compile_addr_step_missing() {
+        let (lines, mut chars) = make_providers("3~p");
+        assert!(compile_address(&lines, &mut chars, &ctx()).is_err());
+    }
+
     #[test]
     fn test_compile_addr_last_line() {
         let (lines, mut chars) = make_providers("$");
@@ -1620,6 +2229,20 @@ mod tests {
         assert_eq!(v2, 3);
     }
 
+    #[test]
+    fn test_compile_multiple_address_range() {
+        let (lines, mut chars) = make_providers("2,~4p");
+        let mut cmd = Rc::new(RefCell::new(Command::default()));
+        let n_addr = compile_address_range(&lines, &mut chars, &mut cmd, &ctx()).unwrap();
+
+        assert_eq!(n_addr, 2);
+        let cmd = cmd.borrow();
+        let addr2 = cmd.addr2.as_ref().unwrap();
+        assert!(matches!(addr2.atype, AddressType::Multiple));
+        assert!(matches!(addr2.value, AddressValue::LineNumber(4)));
+        assert_eq!(chars.current(), 'p');
+    }
+
     #[test]
     fn test_compile_last_address() {
         let (lines, mut chars) = make_providers("$");
@@ -1719,6 +2342,23 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_compile_regex_with_multiline_modifier() {
+        let (lines, mut chars) = make_providers("/^b$/IM");
+        let mut cmd = Rc::new(RefCell::new(Command::default()));
+        compile_address_range(&lines, &mut chars, &mut cmd, &ctx()).unwrap();
+
+        let cmd = cmd.borrow();
+        let addr = cmd.addr1.as_ref().unwrap();
+        assert_eq!(addr.source.as_deref(), Some("/^b$/IM"));
+        if let AddressValue::Regex(Some(re)) = &addr.value {
+            assert!(re.is_match(&mut IOChunk::new_from_str("a\nB\nc")).unwrap());
+            assert!(!re.is_match(&mut IOChunk::new_from_str("a\nbc")).unwrap());
+        } else {
+            panic!("expected a regex address with multiline match");
+        };
+    }
+
     // compile_sequence
     fn empty_line() -> ScriptCharProvider {
         ScriptCharProvider::new("")
@@ -1878,7 +2518,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_literal() {
         let (mut lines, mut chars) = make_providers("/hello/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 1);
         assert!(matches!(&template.parts[0], ReplacementPart::Literal(s) if s == "hello"));
@@ -1887,7 +2527,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_escaped_delimiter() {
         let (mut lines, mut chars) = make_providers(r"/hell\/o/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 1);
         assert!(matches!(&template.parts[0], ReplacementPart::Literal(s) if s == "hell/o"));
@@ -1896,7 +2536,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_backrefs_and_literal() {
         let (mut lines, mut chars) = make_providers("/prefix \\1 and \\2/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 4);
         assert!(matches!(&template.parts[0], ReplacementPart::Literal(s) if s == "prefix "));
@@ -1908,7 +2548,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_whole_match() {
         let (mut lines, mut chars) = make_providers("/The match was: &/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 2);
         assert!(
@@ -1920,7 +2560,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_whole_match_synonym() {
         let (mut lines, mut chars) = make_providers(r"/The match was: \0/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 2);
         assert!(
@@ -1932,7 +2572,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_ampersand() {
         let (mut lines, mut chars) = make_providers("/Simon \\& Garfunkel/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 1);
         assert!(
@@ -1943,7 +2583,7 @@ mod tests {
     #[test]
     fn test_compile_replacement_escape_sequences() {
         let (mut lines, mut chars) = make_providers("/line\\nnewline\\tend/");
-        let template = compile_replacement(&mut lines, &mut chars).unwrap();
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
 
         assert_eq!(template.parts.len(), 1);
         assert!(matches!(
@@ -1952,6 +2592,53 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_compile_replacement_case_conversion() {
+        let (mut lines, mut chars) = make_providers("/\\u\\1x\\L\\2\\E\\l\\U&/");
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
+
+        assert_eq!(template.parts.len(), 9);
+        assert!(matches!(
+            template.parts[0],
+            ReplacementPart::Case(CaseConversion::UpperNext)
+        ));
+        assert!(matches!(&template.parts[2], ReplacementPart::Literal(s) if s == "x"));
+        assert!(matches!(
+            template.parts[3],
+            ReplacementPart::Case(CaseConversion::Lower)
+        ));
+        assert!(matches!(
+            template.parts[5],
+            ReplacementPart::Case(CaseConversion::End)
+        ));
+        assert!(matches!(
+            template.parts[6],
+            ReplacementPart::Case(CaseConversion::LowerNext)
+        ));
+        assert!(matches!(
+            template.parts[7],
+            ReplacementPart::Case(CaseConversion::Upper)
+        ));
+    }
+
+    #[test]
+    fn test_compile_replacement_case_conversion_before_hex_digits() {
+        let (mut lines, mut chars) = make_providers("/\\ubeef\\U00e9/");
+        let template = compile_replacement(&mut lines, &mut chars, &ctx()).unwrap();
+
+        assert_eq!(template.parts.len(), 4);
+        assert!(matches!(
+            template.parts[0],
+            ReplacementPart::Case(CaseConversion::UpperNext)
+        ));
+        assert!(matches!(&template.parts[1], ReplacementPart::Literal(s) if s == "beef"));
+        assert!(matches!(
+            template.parts[2],
+            ReplacementPart::Case(CaseConversion::Upper)
+        ));
+        assert!(matches!(&template.parts[3], ReplacementPart::Literal(s) if s == "00e9"));
+    }
+
     #[test]
     fn test_compile_replacement_line_continuation() {
         let script = vec![
@@ -1962,7 +2649,7 @@ mod tests {
         let first_line = provider.next_line().unwrap().unwrap();
         let mut chars = ScriptCharProvider::new(&first_line);
 
-        let template = compile_replacement(&mut provider, &mut chars).unwrap();
+        let template = compile_replacement(&mut provider, &mut chars, &ctx()).unwrap();
         assert_eq!(template.parts.len(), 1);
         assert!(matches!(
             &template.parts[0],
@@ -1976,7 +2663,7 @@ mod tests {
         let (lines, mut chars) = make_providers("g");
         let mut subst = Substitution::default();
 
-        compile_subst_flags(&lines, &mut chars, &mut subst).unwrap();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
         assert_eq!(subst.occurrence, 0); // 'g' means all occurrences
     }
 
@@ -1985,7 +2672,17 @@ mod tests {
         let (lines, mut chars) = make_providers("p");
         let mut subst = Substitution::default();
 
-        compile_subst_flags(&lines, &mut chars, &mut subst).unwrap();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
+        assert!(subst.print_flag);
+    }
+
+    #[test]
+    fn test_compile_subst_flag_e() {
+        let (lines, mut chars) = make_providers("ep");
+        let mut subst = Substitution::default();
+
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
+        assert!(subst.execute);
         assert!(subst.print_flag);
     }
 
@@ -1994,7 +2691,7 @@ mod tests {
         let (lines, mut chars) = make_providers("I");
         let mut subst = Substitution::default();
 
-        compile_subst_flags(&lines, &mut chars, &mut subst).unwrap();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
         assert!(subst.ignore_case);
     }
 
@@ -2003,7 +2700,7 @@ mod tests {
         let (lines, mut chars) = make_providers("i");
         let mut subst = Substitution::default();
 
-        compile_subst_flags(&lines, &mut chars, &mut subst).unwrap();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
         assert!(subst.ignore_case);
     }
 
@@ -2012,31 +2709,61 @@ mod tests {
         let (lines, mut chars) = make_providers("3");
         let mut subst = Substitution::default();
 
-        compile_subst_flags(&lines, &mut chars, &mut subst).unwrap();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
         assert_eq!(subst.occurrence, 3);
     }
 
     #[test]
-    fn test_compile_subst_flag_g_and_number_should_fail() {
+    fn test_compile_subst_flag_g_and_number() {
         let (lines, mut chars) = make_providers("g3");
         let mut subst = Substitution::default();
 
-        let err = compile_subst_flags(&lines, &mut chars, &mut subst).unwrap_err();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
+        assert_eq!(subst.occurrence, 3);
+        assert!(subst.global);
+    }
+
+    #[test]
+    fn test_compile_subst_flag_number_and_g() {
+        let (lines, mut chars) = make_providers("2g");
+        let mut subst = Substitution::default();
+
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
+        assert_eq!(subst.occurrence, 2);
+        assert!(subst.global);
+    }
+
+    #[test]
+    fn test_compile_subst_flag_one_and_g() {
+        let (lines, mut chars) = make_providers("1g");
+        let mut subst = Substitution::default();
+
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
+        assert_eq!(subst.occurrence, 0); // Same as 'g'
+        assert!(!subst.global);
+    }
+
+    #[test]
+    fn test_compile_subst_flag_multiple_g_should_fail() {
+        let (lines, mut chars) = make_providers("gpg");
+        let mut subst = Substitution::default();
+
+        let err = compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap_err();
         assert!(
             err.to_string()
-                .contains("multiple 'g' or numeric flags in substitute command")
+                .contains("multiple 'g' flags in substitute command")
         );
     }
 
     #[test]
-    fn test_compile_subst_flag_number_and_g_should_fail() {
-        let (lines, mut chars) = make_providers("2g");
+    fn test_compile_subst_flag_multiple_numbers_should_fail() {
+        let (lines, mut chars) = make_providers("2p3");
         let mut subst = Substitution::default();
 
-        let err = compile_subst_flags(&lines, &mut chars, &mut subst).unwrap_err();
+        let err = compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap_err();
         assert!(
             err.to_string()
-                .contains("multiple 'g' or numeric flags in substitute command")
+                .contains("multiple numeric flags in substitute command")
         );
     }
 
@@ -2045,7 +2772,7 @@ mod tests {
         let (lines, mut chars) = make_providers("w ");
         let mut subst = Substitution::default();
 
-        let err = compile_subst_flags(&lines, &mut chars, &mut subst).unwrap_err();
+        let err = compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap_err();
         assert!(err.to_string().contains("missing file path"));
     }
 
@@ -2054,7 +2781,7 @@ mod tests {
         let (lines, mut chars) = make_providers("w out.txt");
         let mut subst = Substitution::default();
 
-        compile_subst_flags(&lines, &mut chars, &mut subst).unwrap();
+        compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap();
         assert_eq!(
             subst.write_file.as_ref().map(|w| w.borrow().path.clone()),
             Some(std::path::PathBuf::from("out.txt"))
@@ -2066,7 +2793,7 @@ mod tests {
         let (lines, mut chars) = make_providers("z");
         let mut subst = Substitution::default();
 
-        let err = compile_subst_flags(&lines, &mut chars, &mut subst).unwrap_err();
+        let err = compile_subst_flags(&lines, &mut chars, &mut subst, &ctx()).unwrap_err();
         assert!(err.to_string().contains("invalid substitute flag"));
     }
 
@@ -2143,50 +2870,175 @@ mod tests {
     // bre_to_ere
     #[test]
     fn test_bre_group_translation() {
-        assert_eq!(bre_to_ere(r"\(abc\)"), "(abc)");
-        assert_eq!(bre_to_ere(r"a\(b\)c"), "a(b)c");
+        assert_eq!(bre_to_ere(r"\(abc\)", false), "(abc)");
+        assert_eq!(bre_to_ere(r"a\(b\)c", false), "a(b)c");
     }
 
     #[test]
     fn test_ere_metacharacters_escaped() {
-        assert_eq!(bre_to_ere(r"a+b?c{1}|(d)"), r"a\+b\?c\{1\}\|\(d\)");
+        assert_eq!(bre_to_ere(r"a+b?c{1}|(d)", false), r"a\+b\?c\{1\}\|\(d\)");
     }
 
     #[test]
     fn test_literal_backslashes_preserved() {
-        assert_eq!(bre_to_ere(r"foo\\bar"), r"foo\\bar");
-        assert_eq!(bre_to_ere(r"\."), r"\.");
+        assert_eq!(bre_to_ere(r"foo\\bar", false), r"foo\\bar");
+        assert_eq!(bre_to_ere(r"\.", false), r"\.");
     }
 
     #[test]
     fn test_character_classes_unchanged() {
-        assert_eq!(bre_to_ere(r"[a-z]"), "[a-z]");
-        assert_eq!(bre_to_ere(r"[^0-9]"), "[^0-9]");
+        assert_eq!(bre_to_ere(r"[a-z]", false), "[a-z]");
+        assert_eq!(bre_to_ere(r"[^0-9]", false), "[^0-9]");
     }
 
     #[test]
     fn test_anchors_and_dot_and_star() {
-        assert_eq!(bre_to_ere(r"^a.*b$"), "^a.*b$");
+        assert_eq!(bre_to_ere(r"^a.*b$", false), "^a.*b$");
     }
 
     #[test]
     fn test_trailing_backslash_is_preserved() {
-        assert_eq!(bre_to_ere(r"abc\"), r"abc\");
+        assert_eq!(bre_to_ere(r"abc\", false), r"abc\");
     }
 
     #[test]
     fn test_caret_escaped_in_middle() {
-        assert_eq!(bre_to_ere(r"^a^[^x]c"), r"^a\^[^x]c");
+        assert_eq!(bre_to_ere(r"^a^[^x]c", false), r"^a\^[^x]c");
     }
 
     #[test]
     fn test_dollar_escaped_in_middle() {
-        assert_eq!(bre_to_ere(r"a$c$"), r"a\$c$");
+        assert_eq!(bre_to_ere(r"a$c$", false), r"a\$c$");
     }
 
     #[test]
     fn test_bre_back_reference() {
-        assert_eq!(bre_to_ere(r"\(.\)\1\(.\)\2"), r"(.)(?:\1)(.)(?:\2)");
+        assert_eq!(bre_to_ere(r"\(.\)\1\(.\)\2", false), r"(.)(?:\1)(.)(?:\2)");
+    }
+
+    #[test]
+    fn test_bre_gnu_operators() {
+        assert_eq!(bre_to_ere(r"a\+b\?c\|d", false), "a+b?c|d");
+        assert_eq!(bre_to_ere(r"a\+b\?c\|d", true), r"a\+b\?c\|d");
+    }
+
+    #[test]
+    fn test_bre_subexpression_anchors() {
+        assert_eq!(bre_to_ere(r"a$\|^b", false), "a$|^b");
+        assert_eq!(bre_to_ere(r"\(^a$\)", false), "(^a$)");
+        assert_eq!(bre_to_ere(r"a$\|^b", true), r"a\$\|\^b");
+    }
+
+    #[test]
+    fn test_bre_intervals() {
+        assert_eq!(bre_to_ere(r"a\{2\}b\{1,\}", false), "a{2}b{1,}");
+        assert_eq!(bre_to_ere(r"a\{,3\}", false), "a{0,3}");
+        assert_eq!(bre_to_ere(r"\(ab\)\{2,4\}", false), "(ab){2,4}");
+    }
+
+    #[test]
+    fn test_bracket_expressions_unchanged() {
+        assert_eq!(bre_to_ere(r"[+?(|]{", false), r"[+?(|]\{");
+        assert_eq!(bre_to_ere(r"[]\(][[:alpha:]]", false), r"[]\(][[:alpha:]]");
+        assert_eq!(bre_to_ere(r"[^]^$]^", false), r"[^]^$]\^");
+        assert_eq!(bre_to_ere(r"[\](", false), r"[\]\(");
+    }
+
+    // translate_to_engine
+    fn translate(pattern: &str) -> UResult<String> {
+        let (lines, chars) = make_providers("");
+        translate_to_engine(&lines, &chars, pattern, false, false)
+    }
+
+    #[test]
+    fn test_buffer_anchors_translated() {
+        assert_eq!(translate(r"\`a\'").unwrap(), r"\Aa\z");
+        assert_eq!(translate(r"a\\'\.").unwrap(), r"a\\'\.");
+        assert_eq!(translate(r"abc\").unwrap(), r"abc\");
+        assert_eq!(translate(r"[\`]").unwrap(), r"[\\`]");
+    }
+
+    #[test]
+    fn test_word_escapes_translated() {
+        assert_eq!(translate(r"\<a\>").unwrap(), r"\b{start}a\b{end}");
+        assert_eq!(translate(r"\b\B\w\W\s\S").unwrap(), r"\b\B\w\W\s\S");
+    }
+
+    #[test]
+    fn test_gnu_escapes_literal_with_posix() {
+        let (lines, chars) = make_providers("");
+        assert_eq!(
+            translate_to_engine(&lines, &chars, r"\`\<\b\w\s\>\'\.", true, false).unwrap(),
+            r"`<bws>'\."
+        );
+    }
+
+    #[test]
+    fn test_multiline_negated_bracket_excludes_newline() {
+        let (lines, chars) = make_providers("");
+        assert_eq!(
+            translate_to_engine(&lines, &chars, "[^a][b]", false, true).unwrap(),
+            "[^\na][b]"
+        );
+        assert_eq!(translate("[^a]").unwrap(), "[^a]");
+    }
+
+    #[test]
+    fn test_posix_classes_translated() {
+        assert_eq!(translate("[[:digit:]]").unwrap(), "[0-9]");
+        assert_eq!(translate("[^[:alpha:]_]").unwrap(), r"[^\p{Alphabetic}_]");
+        assert_eq!(translate("[[:graph:]]").unwrap(), r"[[^\p{C}\p{Z}]]");
+        assert!(
+            translate("[[:alpah:]]")
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid character class name")
+        );
+    }
+
+    #[test]
+    fn test_equivalence_and_collating_translated() {
+        assert_eq!(translate("[[=e=]x]").unwrap(), "[ex]");
+        assert_eq!(translate("[[.-.][.].]]").unwrap(), r"[\-\]]");
+        assert_eq!(translate("[[.a.]-[=c=]]").unwrap(), "[a-c]");
+        assert!(
+            translate("[[.ch.]]")
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid collation character")
+        );
+    }
+
+    #[test]
+    fn test_bracket_edge_characters_escaped() {
+        assert_eq!(translate("[]a]").unwrap(), r"[\]a]");
+        assert_eq!(translate("[^]a]").unwrap(), r"[^\]a]");
+        assert_eq!(translate("[-a]").unwrap(), r"[\-a]");
+        assert_eq!(translate("[a-]").unwrap(), r"[a\-]");
+        assert_eq!(translate("[!--]").unwrap(), r"[!-\-]");
+        assert_eq!(translate("[a^&&~~[]").unwrap(), r"[a\^\&\&\~\~\[]");
+        assert_eq!(translate(r"[\]\n]").unwrap(), r"[\\]\n]");
+        assert_eq!(translate(r"[[]").unwrap(), r"[\[]");
+        assert_eq!(translate(r"[a\\]").unwrap(), r"[a\\\\]");
+    }
+
+    #[test]
+    fn test_invalid_bracket_ranges() {
+        for pattern in ["[z-a]", "[[:alpha:]-z]", "[a-[:digit:]]"] {
+            assert!(
+                translate(pattern)
+                    .unwrap_err()
+                    .to_string()
+                    .contains("Invalid range end"),
+                "pattern {pattern}"
+            );
+        }
+        assert!(
+            translate("a[b")
+                .unwrap_err()
+                .to_string()
+                .contains("Unmatched [")
+        );
     }
 
     // patch_block_endings
@@ -2367,6 +3219,38 @@ mod tests {
         }
     }
 
+    // compile_version_command
+    #[test]
+    fn test_compile_version_command() {
+        for version in ["", "4", "4.2", "4.9.0"] {
+            let script = format!("v {version}");
+            let (mut lines, mut chars) = make_providers(&script);
+            let mut cmd = Command::default();
+            assert!(
+                compile_version_command(&mut lines, &mut chars, &mut cmd, &mut ctx()).is_ok(),
+                "{script}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile_version_command_newer() {
+        for (version, message) in [
+            ("5", "expected newer version of sed"),
+            ("4.10", "expected newer version of sed"),
+            ("4.9.1", "expected newer version of sed"),
+            ("4..1", "invalid version `4..1'"),
+        ] {
+            let script = format!("v {version}");
+            let (mut lines, mut chars) = make_providers(&script);
+            let mut cmd = Command::default();
+            let err = compile_version_command(&mut lines, &mut chars, &mut cmd, &mut ctx())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(message), "{script}: {err}");
+        }
+    }
+
     // populate_label_map
     fn command_with_data(data: CommandData) -> Rc<RefCell<Command>> {
         Rc::new(RefCell::new(Command {
@@ -2744,4 +3628,204 @@ mod tests {
         let err = read_file_path(&lines, &mut chars).unwrap_err();
         assert!(err.to_string().contains("missing file path"));
     }
+
+    // check_sandbox
+    #[test]
+    fn test_sandbox_rejects_read() {
+        let (mut lines, mut chars) = make_providers("r /etc/motd");
+        let mut cmd = Command::default();
+        let mut context = ProcessingContext {
+            sandbox: true,
+            ..Default::default()
+        };
+
+        let err =
+            compile_read_file_command(&mut lines, &mut chars, &mut cmd, &mut context).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("e/r/w commands disabled in sandbox mode")
+        );
+    }
+
+    #[test]
+    fn test_sandbox_rejects_write() {
+        let (mut lines, mut chars) = make_providers("w /dev/null");
+        let mut cmd = Command::default();
+        let mut context = ProcessingContext {
+            sandbox: true,
+            ..Default::default()
+        };
+
+        let err =
+            compile_write_file_command(&mut lines, &mut chars, &mut cmd, &mut context).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("e/r/w commands disabled in sandbox mode")
+        );
+    }
+
+    #[test]
+    fn test_sandbox_rejects_subst_write_flag() {
+        let (lines, mut chars) = make_providers("gw /dev/null");
+        let mut subst = Substitution::default();
+        let context = ProcessingContext {
+            sandbox: true,
+            ..Default::default()
+        };
+
+        let err = compile_subst_flags(&lines, &mut chars, &mut subst, &context).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("e/r/w commands disabled in sandbox mode")
+        );
+    }
+
+    #[test]
+    fn test_sandbox_rejects_exec() {
+        let (mut lines, mut chars) = make_providers("e ls");
+        let mut cmd = Command::default();
+        let mut context = ProcessingContext {
+            sandbox: true,
+            ..Default::default()
+        };
+
+        let err = compile_exec_command(&mut lines, &mut chars, &mut cmd, &mut context).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("e/r/w commands disabled in sandbox mode")
+        );
+    }
+
+    #[test]
+    fn test_sandbox_rejects_subst_exec_flag() {
+        let (lines, mut chars) = make_providers("e");
+        let mut subst = Substitution::default();
+        let context = ProcessingContext {
+            sandbox: true,
+            ..Default::default()
+        };
+
+        let err = compile_subst_flags(&lines, &mut chars, &mut subst, &context).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("e/r/w commands disabled in sandbox mode")
+        );
+    }
+
+    // compile_exec_command
+    #[test]
+    fn test_compile_exec_with_command() {
+        let (mut lines, mut chars) = make_providers("e  echo a; echo b");
+        let mut cmd = Command::default();
+
+        compile_exec_command(&mut lines, &mut chars, &mut cmd, &mut ctx()).unwrap();
+        match &cmd.data {
+            CommandData::ShellCommand(Some(command)) => assert_eq!(command, "echo a; echo b"),
+            _ => panic!("Expected ShellCommand(Some)"),
+        }
+    }
+
+    #[test]
+    fn test_compile_exec_without_command() {
+        let (mut lines, mut chars) = make_providers("e");
+        let mut cmd = Command::default();
+
+        compile_exec_command(&mut lines, &mut chars, &mut cmd, &mut ctx()).unwrap();
+        assert!(matches!(cmd.data, CommandData::ShellCommand(None)));
+    }
+
+    // posix_check
+    fn compile_posix(script: &str) -> UResult<Option<Rc<RefCell<Command>>>> {
+        let mut context = ProcessingContext {
+            posix: true,
+            ..Default::default()
+        };
+        compile(
+            vec![ScriptValue::StringVal(script.to_string())],
+            &mut context,
+        )
+    }
+
+    #[test]
+    fn test_posix_accepts_portable_script() {
+        assert!(compile_posix("1,/x/{s/a\\(b\\)/&\\1\\n/g;y/ab/cd/;l;q;}").is_ok());
+    }
+
+    #[test]
+    fn test_posix_rejects_extensions() {
+        for (script, message) in [
+            ("Q", "1:1: error: the Q command"),
+            ("q5", "1:2: error: an argument to the q command"),
+            ("l 3", "1:3: error: an argument to the l command"),
+            ("1,+2p", "1:3: error: a +N address"),
+            ("0~2p", "1:2: error: a first~step address"),
+            ("0,/x/p", "1:6: error: the 0,/re/ address"),
+            ("1,~2p", "1:3: error: a ~N address"),
+            ("/x/Ip", "1:4: error: the I modifier"),
+            ("s/x/y/gI", "1:8: error: the I modifier"),
+            ("/x/Mp", "1:4: error: the M modifier"),
+            ("s/x/y/M", "1:7: error: the M modifier"),
+            ("s/x/y/2g", "1:8: error: combining the g and numeric flags"),
+            ("s/x/\\0/", "1:6: error: the \\0 reference"),
+            ("s/x/\\u0041/", "1:6: error: the \\u escape"),
+            ("s/x/\\L&/", "1:6: error: the \\L escape"),
+            ("/\\x41/p", "1:3: error: the \\x escape"),
+            ("1F", "1:2: error: the F command"),
+            ("z", "1:1: error: the z command"),
+            ("W out", "1:1: error: the W command"),
+            ("R in", "1:1: error: the R command"),
+            ("T", "1:1: error: the T command"),
+            ("v", "1:1: error: the v command"),
+        ] {
+            let err = compile_posix(script).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("{message} is not allowed with --posix")),
+                "{script}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_address_zero() {
+        let scripts = vec![ScriptValue::StringVal("0,/x/p".to_string())];
+        assert!(compile(scripts, &mut ctx()).is_ok());
+
+        for script in ["0p", "0,3p", "0,$p", "0~0p", "0,+1p", "0!p"] {
+            let scripts = vec![ScriptValue::StringVal(script.to_string())];
+            let err = compile(scripts, &mut ctx()).unwrap_err();
+            assert!(
+                err.to_string().contains("invalid usage of line address 0"),
+                "{script}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_address_error_is_reported() {
+        let scripts = vec![ScriptValue::StringVal("1,p".to_string())];
+        let err = compile(scripts, &mut ctx()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("1:3: error: expected context address")
+        );
+    }
+
+    // needs_last_line
+    #[test]
+    fn test_needs_last_line() {
+        for (script, expected) in [
+            ("1p;/x/d;4,+2p", false),
+            ("2,5c\\\nfoo", true),
+            ("$p", true),
+            ("1{/x/,$d;}", true),
+        ] {
+            let mut context = ctx();
+            compile(
+                vec![ScriptValue::StringVal(script.to_string())],
+                &mut context,
+            )
+            .unwrap();
+            assert_eq!(context.needs_last_line, expected, "{script}");
+        }
+    }
 }
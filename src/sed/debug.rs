@@ -0,0 +1,377 @@
+// Annotate program compilation and execution (--debug)
+//
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Diomidis Spinellis
+//
+// This file is part of the uutils sed package.
+// It is licensed under the MIT License.
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+use crate::sed::command::{
+    Address, AddressType, AddressValue, CaseConversion, Command, CommandData, ProcessingContext,
+    ReplacementPart, Substitution, Transliteration,
+};
+use crate::sed::compiler::escape_delimiter;
+use crate::sed::fast_io::{IOChunk, OutputBuffer};
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use uucore::display::Quotable;
+use uucore::error::UResult;
+
+/// Indentation for each level of block nesting in the program listing
+const INDENT: &str = "  ";
+
+/// Return the address in canonical script form.
+fn format_address(addr: &Address) -> String {
+    match (&addr.atype, &addr.value) {
+        (AddressType::Re, _) => addr.source.clone().unwrap_or_else(|| "//".to_string()),
+        (AddressType::Last, _) => "$".to_string(),
+        (AddressType::RelLine, AddressValue::LineNumber(n)) => format!("+{n}"),
+        (AddressType::Multiple, AddressValue::LineNumber(n)) => format!("~{n}"),
+        (_, AddressValue::LineNumber(n)) => n.to_string(),
+        (_, AddressValue::Step(first, step)) => format!("{first}~{step}"),
+        (_, AddressValue::Regex(_)) => "//".to_string(),
+    }
+}
+
+/// Return the text of an a, c, or i command in canonical script form.
+fn format_text(text: &str) -> String {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    text.replace('\\', "\\\\").replace('\n', "\\\n")
+}
+
+/// Return the replacement of an s command in canonical script form.
+fn format_replacement(parts: &[ReplacementPart]) -> String {
+    let mut result = String::new();
+    for part in parts {
+        match part {
+            ReplacementPart::Literal(s) => {
+                for c in s.chars() {
+                    match c {
+                        '\\' | '&' | '/' => {
+                            result.push('\\');
+                            result.push(c);
+                        }
+                        '\n' => result.push_str("\\n"),
+                        c => result.push(c),
+                    }
+                }
+            }
+            ReplacementPart::WholeMatch => result.push('&'),
+            ReplacementPart::Group(n) => result.push_str(&format!("\\{n}")),
+            ReplacementPart::Case(conversion) => result.push_str(match conversion {
+                CaseConversion::Upper => "\\U",
+                CaseConversion::Lower => "\\L",
+                CaseConversion::UpperNext => "\\u",
+                CaseConversion::LowerNext => "\\l",
+                CaseConversion::End => "\\E",
+            }),
+        }
+    }
+    result
+}
+
+/// Return an s command's arguments in canonical script form.
+fn format_substitution(subst: &Substitution) -> String {
+    let mut result = format!(
+        "/{}/{}/",
+        escape_delimiter(&subst.pattern, '/'),
+        format_replacement(&subst.replacement.parts)
+    );
+    match subst.occurrence {
+        0 => result.push('g'),
+        1 => (),
+        n => result.push_str(&n.to_string()),
+    }
+    if subst.global {
+        result.push('g');
+    }
+    if subst.print_flag {
+        result.push('p');
+    }
+    if subst.ignore_case {
+        result.push('I');
+    }
+    if subst.multiline {
+        result.push('M');
+    }
+    if subst.execute {
+        result.push('e');
+    }
+    if let Some(writer) = &subst.write_file {
+        result.push_str(&format!("w {}", writer.borrow().path.display()));
+    }
+    result
+}
+
+/// Return a y command's arguments in canonical script form.
+fn format_transliteration(trans: &Transliteration) -> String {
+    let escape = |c: char| match c {
+        '\\' => "\\\\".to_string(),
+        '/' => "\\/".to_string(),
+        '\n' => "\\n".to_string(),
+        c => c.to_string(),
+    };
+    let (source, target): (String, String) = trans
+        .mappings()
+        .into_iter()
+        .map(|(from, to)| (escape(from), escape(to)))
+        .unzip();
+    format!("/{source}/{target}/")
+}
+
+/// Return the command, without the commands of a block,
+/// in canonical script form.
+pub fn format_command(cmd: &Command) -> String {
+    let mut result = String::new();
+
+    if let Some(addr1) = &cmd.addr1 {
+        result.push_str(&format_address(addr1));
+    }
+    if let Some(addr2) = &cmd.addr2 {
+        result.push(',');
+        result.push_str(&format_address(addr2));
+    }
+    if cmd.non_select {
+        result.push('!');
+    }
+    if !result.is_empty() {
+        result.push(' ');
+    }
+
+    result.push(cmd.code);
+    match (&cmd.data, cmd.code) {
+        (CommandData::Text(text), _) => {
+            result.push_str("\\\n");
+            result.push_str(&format_text(text));
+        }
+        (CommandData::BranchTarget(Some(target)), 'b' | 't' | 'T') => {
+            if let CommandData::Label(Some(label)) = &target.borrow().data {
+                result.push(' ');
+                result.push_str(label);
+            }
+        }
+        (CommandData::Label(Some(label)), ':') => result.push_str(label),
+        (CommandData::Label(Some(label)), _) => {
+            result.push(' ');
+            result.push_str(label);
+        }
+        (CommandData::Path(path), _) => result.push_str(&format!(" {}", path.display())),
+        (CommandData::NamedReader(reader), _) => {
+            result.push_str(&format!(" {}", reader.borrow().path.display()));
+        }
+        (CommandData::NamedWriter(writer), _) => {
+            result.push_str(&format!(" {}", writer.borrow().path.display()));
+        }
+        (CommandData::ShellCommand(Some(command)), _) => {
+            result.push(' ');
+            result.push_str(command);
+        }
+        (CommandData::Number(0), 'q' | 'Q') => (),
+        (CommandData::Number(n), _) => result.push_str(&format!(" {n}")),
+        (CommandData::Substitution(subst), _) => result.push_str(&format_substitution(subst)),
+        (CommandData::Transliteration(trans), _) => {
+            result.push_str(&format_transliteration(trans));
+        }
+        _ => (),
+    }
+    result
+}
+
+/// Append to result the listing of the commands starting from cur
+/// and finishing before end (the command following a block).
+fn format_sequence(
+    result: &mut String,
+    mut cur: Option<Rc<RefCell<Command>>>,
+    end: &Option<Rc<RefCell<Command>>>,
+    depth: usize,
+) {
+    while let Some(rc_cmd) = cur {
+        if let Some(end_cmd) = end
+            && Rc::ptr_eq(&rc_cmd, end_cmd)
+        {
+            break;
+        }
+
+        let cmd = rc_cmd.borrow();
+        let indent = INDENT.repeat(depth);
+        result.push_str(&indent);
+        result.push_str(&format_command(&cmd));
+        result.push('\n');
+
+        if cmd.code == '{' {
+            // The block's last command has been patched to continue
+            // with the command following the block.
+            let block_end = cmd.next.clone().or(end.clone());
+            if let CommandData::BranchTarget(Some(body)) = &cmd.data {
+                format_sequence(result, Some(body.clone()), &block_end, depth + 1);
+            }
+            result.push_str(&indent);
+            result.push_str("}\n");
+        }
+
+        cur = cmd.next.clone();
+    }
+}
+
+/// Return the compiled program in canonical script form.
+pub fn format_program(head: &Option<Rc<RefCell<Command>>>) -> String {
+    let mut result = String::new();
+    format_sequence(&mut result, head.clone(), &None, 1);
+    result
+}
+
+/// Return the contents of a pattern or hold space in a single-line form.
+fn format_space(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\0' => result.push_str("\\0"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Output the specified debug message.
+/// Messages go to the standard output, in order with the rest of the
+/// output, unless the output is being written to a file in place.
+fn write_debug(output: &mut OutputBuffer, context: &ProcessingContext, msg: String) -> UResult<()> {
+    if context.in_place {
+        io::stdout().write_all(msg.as_bytes())?;
+    } else {
+        output.write_str(msg)?;
+    }
+    Ok(())
+}
+
+/// Output the compiled program before any input is processed.
+pub fn trace_program(head: &Option<Rc<RefCell<Command>>>) -> UResult<()> {
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "SED PROGRAM:\n{}", format_program(head))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Output the state at the beginning of an execution cycle.
+pub fn trace_cycle_start(
+    output: &mut OutputBuffer,
+    context: &ProcessingContext,
+    pattern: &IOChunk,
+) -> UResult<()> {
+    let msg = format!(
+        "INPUT:   {} line {}\nPATTERN: {}\nHOLD:    {}\n",
+        context.input_name.quote(),
+        context.line_number,
+        format_space(&String::from_utf8_lossy(pattern.as_bytes())),
+        format_space(&context.hold.content)
+    );
+    write_debug(output, context, msg)
+}
+
+/// Output a command about to be executed and whether its address matched.
+pub fn trace_command(
+    output: &mut OutputBuffer,
+    context: &ProcessingContext,
+    cmd: &Command,
+    matched: bool,
+) -> UResult<()> {
+    let status = match (cmd.addr1.is_some(), matched) {
+        (false, _) => "",
+        (true, true) => " [address matched]",
+        (true, false) => " [address not matched]",
+    };
+    let msg = format!("COMMAND: {}{status}\n", format_command(cmd));
+    write_debug(output, context, msg)
+}
+
+/// Output the spaces modified by the just executed command.
+pub fn trace_spaces(
+    output: &mut OutputBuffer,
+    context: &ProcessingContext,
+    pattern: &IOChunk,
+    code: char,
+) -> UResult<()> {
+    if matches!(code, 'e' | 'g' | 'G' | 's' | 'x' | 'y' | 'z') {
+        let msg = format!(
+            "PATTERN: {}\n",
+            format_space(&String::from_utf8_lossy(pattern.as_bytes()))
+        );
+        write_debug(output, context, msg)?;
+    }
+    if matches!(code, 'h' | 'H' | 'x') {
+        let msg = format!("HOLD:    {}\n", format_space(&context.hold.content));
+        write_debug(output, context, msg)?;
+    }
+    Ok(())
+}
+
+/// Output the end of an execution cycle.
+pub fn trace_cycle_end(output: &mut OutputBuffer, context: &ProcessingContext) -> UResult<()> {
+    write_debug(output, context, "END-OF-CYCLE:\n".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sed::compiler::compile;
+    use crate::sed::script_line_provider::ScriptValue;
+
+    /// Compile the specified script and return its listing.
+    fn listing(script: &str) -> String {
+        let mut context = ProcessingContext::default();
+        let program = compile(
+            vec![ScriptValue::StringVal(script.to_string())],
+            &mut context,
+        )
+        .unwrap();
+        format_program(&program)
+    }
+
+    #[test]
+    fn test_format_simple_commands() {
+        assert_eq!(listing("p;x;="), "  p\n  x\n  =\n");
+    }
+
+    #[test]
+    fn test_format_addresses() {
+        assert_eq!(
+            listing("1,/a\\/b/I!d;$p;5,+2p"),
+            "  1,/a\\/b/I! d\n  $ p\n  5,+2 p\n"
+        );
+    }
+
+    #[test]
+    fn test_format_nested_blocks() {
+        assert_eq!(
+            listing("1{h;/x/{s/a/b/g;};p;};G"),
+            "  1 {\n    h\n    /x/ {\n      s/a/b/g\n    }\n    p\n  }\n  G\n"
+        );
+    }
+
+    #[test]
+    fn test_format_branches() {
+        assert_eq!(
+            listing(":a\ns/\\(x\\)/&\\n\\1\\\\/2p\nta\nb"),
+            "  :a\n  s/\\(x\\)/&\\n\\1\\\\/2p\n  t a\n  b\n"
+        );
+    }
+
+    #[test]
+    fn test_format_text_and_numbers() {
+        assert_eq!(
+            listing("a\\\nfoo\\\nbar\nq5\nl 3\ny/ab\\//c\\\\d/"),
+            "  a\\\nfoo\\\nbar\n  q 5\n  l 3\n  y/\\/ab/dc\\\\/\n"
+        );
+    }
+
+    #[test]
+    fn test_format_space() {
+        assert_eq!(format_space("a\nb\tc\0"), "a\\nb\\tc\\0");
+    }
+}
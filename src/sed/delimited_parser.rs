@@ -8,7 +8,8 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
-use crate::sed::error_handling::compilation_error;
+use crate::sed::command::ProcessingContext;
+use crate::sed::error_handling::{compilation_error, posix_check};
 use crate::sed::script_char_provider::ScriptCharProvider;
 use crate::sed::script_line_provider::ScriptLineProvider;
 
@@ -176,10 +177,24 @@ pub fn parse_char_escape(line: &mut ScriptCharProvider) -> Option<char> {
     }
 }
 
+/// Parse a character escape as parse_char_escape does, failing with
+/// an error if the escape is a GNU extension used under --posix.
+pub fn parse_script_escape(
+    lines: &ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    context: &ProcessingContext,
+) -> UResult<Option<char>> {
+    if let c @ ('c' | 'd' | 'o' | 'u' | 'U' | 'x') = line.current() {
+        posix_check(lines, line, context, format!("the \\{c} escape"))?;
+    }
+    Ok(parse_char_escape(line))
+}
+
 /// Parse a POSIX RE character class returning it as a string.
 /// This functionality is needed to avoid terminating delimited
 /// sequences when a delimiter appears within a character class.
-/// While at it, handle escaped characters for the sake of consistency.
+/// As in POSIX, a backslash is a literal character within the class,
+/// but as in GNU sed, character escapes such as `\n` are decoded.
 fn parse_character_class(
     lines: &ScriptLineProvider,
     line: &mut ScriptCharProvider,
@@ -261,8 +276,6 @@ fn parse_character_class(
                 } else {
                     // Not a POSIX construct — treat as literal
                     result.push('[');
-                    result.push(marker);
-                    line.advance();
                     continue;
                 }
             } else {
@@ -279,11 +292,8 @@ fn parse_character_class(
             }
             match parse_char_escape(line) {
                 Some(decoded) => result.push(decoded),
-                None => {
-                    result.push('\\');
-                    result.push(line.current());
-                    line.advance();
-                }
+                // A literal backslash; the next character is scanned normally.
+                None => result.push('\\'),
             }
         } else {
             result.push(ch);
@@ -310,10 +320,81 @@ fn scan_delimiter(lines: &ScriptLineProvider, line: &mut ScriptCharProvider) ->
     Ok(delimiter)
 }
 
+/// The largest repetition count allowed in an interval expression
+const RE_DUP_MAX: usize = 32767;
+
+/// Parse an optional interval expression repetition count, appending
+/// its digits to result.
+fn parse_interval_count(
+    lines: &ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    result: &mut String,
+) -> UResult<Option<usize>> {
+    let mut count = None;
+    while !line.eol() && line.current().is_ascii_digit() {
+        let value = count.unwrap_or(0) * 10 + line.current().to_digit(10).unwrap() as usize;
+        if value > RE_DUP_MAX {
+            return compilation_error(lines, line, "Regular expression too big");
+        }
+        count = Some(value);
+        result.push(line.current());
+        line.advance();
+    }
+    Ok(count)
+}
+
+/// Parse the BRE interval expression `\{n,m\}` whose `{` is the current
+/// line character, appending it to result.
+/// Errors are reported at the column where the interval becomes invalid.
+fn parse_interval(
+    lines: &ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    delimiter: char,
+    result: &mut String,
+) -> UResult<()> {
+    if result.is_empty() {
+        return compilation_error(lines, line, "Invalid preceding regular expression");
+    }
+    result.push_str("\\{");
+    line.advance();
+
+    let min = parse_interval_count(lines, line, result)?;
+    let max = if !line.eol() && line.current() == ',' {
+        result.push(',');
+        line.advance();
+        parse_interval_count(lines, line, result)?
+    } else if min.is_none() {
+        return compilation_error(lines, line, "Invalid content of \\{\\}");
+    } else {
+        min
+    };
+    if let (Some(min), Some(max)) = (min, max)
+        && min > max
+    {
+        return compilation_error(lines, line, "Invalid content of \\{\\}");
+    }
+
+    for expected in ['\\', '}'] {
+        if line.eol() || line.current() == delimiter {
+            return compilation_error(lines, line, "Unmatched \\{");
+        }
+        if line.current() != expected {
+            return compilation_error(lines, line, "Invalid content of \\{\\}");
+        }
+        line.advance();
+    }
+    result.push_str("\\}");
+    Ok(())
+}
+
 /// Parse the regular expression delimited by the current line
 /// character and return it as a string.
 /// On return the line is on the closing delimiter.
-pub fn parse_regex(lines: &ScriptLineProvider, line: &mut ScriptCharProvider) -> UResult<String> {
+pub fn parse_regex(
+    lines: &ScriptLineProvider,
+    line: &mut ScriptCharProvider,
+    context: &ProcessingContext,
+) -> UResult<String> {
     let delimiter = scan_delimiter(lines, line)?;
     let mut result = String::new();
 
@@ -335,7 +416,17 @@ pub fn parse_regex(lines: &ScriptLineProvider, line: &mut ScriptCharProvider) ->
                     line.advance();
                     continue;
                 }
-                match parse_char_escape(line) {
+                if line.current() == '{' && !context.regex_extended {
+                    parse_interval(lines, line, delimiter, &mut result)?;
+                    continue;
+                }
+                if line.current() == 'b' {
+                    // A word boundary rather than a backspace
+                    result.push_str("\\b");
+                    line.advance();
+                    continue;
+                }
+                match parse_script_escape(lines, line, context)? {
                     Some(decoded) => result.push(decoded),
                     None => {
                         // Pass through \<any> to RE engine for further treatment
@@ -360,6 +451,7 @@ pub fn parse_regex(lines: &ScriptLineProvider, line: &mut ScriptCharProvider) ->
 pub fn parse_transliteration(
     lines: &ScriptLineProvider,
     line: &mut ScriptCharProvider,
+    context: &ProcessingContext,
 ) -> UResult<String> {
     let delimiter = scan_delimiter(lines, line)?;
     let mut result = String::new();
@@ -377,7 +469,7 @@ pub fn parse_transliteration(
                     line.advance();
                     continue;
                 }
-                match parse_char_escape(line) {
+                match parse_script_escape(lines, line, context)? {
                     Some(decoded) => result.push(decoded),
                     None => {
                         // Pass through \<any> to tr for literal use
@@ -692,10 +784,34 @@ mod tests {
 
     #[test]
     fn test_escaped_delimiter() {
-        let mut line = char_provider_from("[a\\]bc]");
+        let mut line = char_provider_from("[a\\/bc]");
         let lines = test_lines();
         let result = parse_character_class(&lines, &mut line).unwrap();
-        assert_eq!(result, "[a\\]bc]");
+        assert_eq!(result, "[a\\/bc]");
+    }
+
+    #[test]
+    fn test_literal_backslash() {
+        for (input, expected) in [
+            ("[a\\]bc]", "[a\\]"),
+            ("[\\]", "[\\]"),
+            ("[\\\\]", "[\\\\]"),
+        ] {
+            let mut line = char_provider_from(input);
+            let lines = test_lines();
+            let result = parse_character_class(&lines, &mut line).unwrap();
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_literal_open_bracket() {
+        for (input, expected) in [("[[]", "[[]"), ("[a[]", "[a[]"), ("[^[]x]", "[^[]")] {
+            let mut line = char_provider_from(input);
+            let lines = test_lines();
+            let result = parse_character_class(&lines, &mut line).unwrap();
+            assert_eq!(result, expected);
+        }
     }
 
     #[test]
@@ -758,7 +874,7 @@ mod tests {
     #[test]
     fn test_simple_regex() {
         let (lines, mut line) = make_providers("/abc/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "abc");
         assert_eq!(line.current(), '/');
     }
@@ -766,7 +882,7 @@ mod tests {
     #[test]
     fn test_regex_with_escaped_delimiter() {
         let (lines, mut line) = make_providers("/ab\\/c/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "ab/c");
         assert_eq!(line.current(), '/');
     }
@@ -774,7 +890,7 @@ mod tests {
     #[test]
     fn test_regex_with_capture() {
         let (lines, mut line) = make_providers(r"/\(.\)/c/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, r"\(.\)");
         assert_eq!(line.current(), '/');
     }
@@ -782,7 +898,7 @@ mod tests {
     #[test]
     fn test_regex_with_escape_sequence() {
         let (lines, mut line) = make_providers("/ab\\n/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "ab\n");
         assert_eq!(line.current(), '/');
     }
@@ -790,21 +906,21 @@ mod tests {
     #[test]
     fn errors_on_unterminated_regex() {
         let (lines, mut line) = make_providers("/unterminated");
-        let err = parse_regex(&lines, &mut line).unwrap_err();
+        let err = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap_err();
         assert!(err.to_string().contains("unterminated regular expression"));
     }
 
     #[test]
     fn errors_on_esc_at_re_eol() {
         let (lines, mut line) = make_providers("/foo\\");
-        let err = parse_regex(&lines, &mut line).unwrap_err();
+        let err = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap_err();
         assert!(err.to_string().contains("unterminated regular expression"));
     }
 
     #[test]
     fn errors_on_backslash_delimiter() {
         let (lines, mut line) = make_providers("\\bad");
-        let err = parse_regex(&lines, &mut line).unwrap_err();
+        let err = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap_err();
         assert!(
             err.to_string()
                 .contains("\\ cannot be used as a string delimiter")
@@ -814,7 +930,7 @@ mod tests {
     #[test]
     fn test_regex_with_character_class() {
         let (lines, mut line) = make_providers("/[a-z]/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "[a-z]");
         assert_eq!(line.current(), '/');
     }
@@ -822,7 +938,7 @@ mod tests {
     #[test]
     fn test_regex_with_bracket_delimiter() {
         let (lines, mut line) = make_providers("[abc[");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "abc");
         assert_eq!(line.current(), '[');
     }
@@ -830,7 +946,7 @@ mod tests {
     #[test]
     fn test_bracket_regex_with_bracket_delimiter() {
         let (lines, mut line) = make_providers("[a\\[0-9]bc[");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "a[0-9]bc");
         assert_eq!(line.current(), '[');
     }
@@ -838,7 +954,7 @@ mod tests {
     #[test]
     fn test_regex_with_escaped_bracket_in_character_class() {
         let (lines, mut line) = make_providers("/[a\\]z]/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "[a\\]z]");
         assert_eq!(line.current(), '/');
     }
@@ -846,7 +962,7 @@ mod tests {
     #[test]
     fn test_regex_with_delimiter_inside_character_class() {
         let (lines, mut line) = make_providers("/[a/c]/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "[a/c]");
         assert_eq!(line.current(), '/');
     }
@@ -854,16 +970,65 @@ mod tests {
     #[test]
     fn test_regex_with_escaped_paren_and_backslash() {
         let (lines, mut line) = make_providers("/\\(\\\\/");
-        let parsed = parse_regex(&lines, &mut line).unwrap();
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "\\(\\\\");
         assert_eq!(line.current(), '/');
     }
 
+    #[test]
+    fn test_regex_with_word_boundary() {
+        let (lines, mut line) = make_providers(r"/\ba\t\b/");
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
+        assert_eq!(parsed, "\\ba\t\\b");
+    }
+
+    #[test]
+    fn test_regex_with_intervals() {
+        let (lines, mut line) = make_providers(r"/a\{2\}b\{1,\}c\{,3\}d\{2,4\}/");
+        let parsed = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap();
+        assert_eq!(parsed, r"a\{2\}b\{1,\}c\{,3\}d\{2,4\}");
+        assert_eq!(line.current(), '/');
+    }
+
+    #[test]
+    fn test_regex_with_ere_braces() {
+        let context = ProcessingContext {
+            regex_extended: true,
+            ..Default::default()
+        };
+        let (lines, mut line) = make_providers(r"/a{2}\{x/");
+        let parsed = parse_regex(&lines, &mut line, &context).unwrap();
+        assert_eq!(parsed, r"a{2}\{x");
+    }
+
+    #[test]
+    fn errors_on_malformed_intervals() {
+        for (script, message) in [
+            (
+                r"/\{2\}/",
+                r":3: error: Invalid preceding regular expression",
+            ),
+            (r"/a\{x\}/", r":5: error: Invalid content of \{\}"),
+            (r"/a\{\}/", r":5: error: Invalid content of \{\}"),
+            (r"/a\{3,2\}/", r":8: error: Invalid content of \{\}"),
+            (r"/a\{2,3x/", r":8: error: Invalid content of \{\}"),
+            (r"/a\{2\x/", r":7: error: Invalid content of \{\}"),
+            (r"/a\{2/", r":6: error: Unmatched \{"),
+            (r"/a\{2", r":6: error: Unmatched \{"),
+            (r"/a\{32768\}/", r":9: error: Regular expression too big"),
+        ] {
+            let (lines, mut line) = make_providers(script);
+            let err = parse_regex(&lines, &mut line, &ProcessingContext::default()).unwrap_err();
+            assert!(err.to_string().ends_with(message), "script {script}: {err}");
+        }
+    }
+
     // parse_transliteration
     #[test]
     fn test_simple_transliteration() {
         let (lines, mut line) = make_providers("/abc/");
-        let parsed = parse_transliteration(&lines, &mut line).unwrap();
+        let parsed =
+            parse_transliteration(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "abc");
         assert_eq!(line.current(), '/');
     }
@@ -871,7 +1036,8 @@ mod tests {
     #[test]
     fn test_transliteration_with_escaped_delimiter() {
         let (lines, mut line) = make_providers("/ab\\/c/");
-        let parsed = parse_transliteration(&lines, &mut line).unwrap();
+        let parsed =
+            parse_transliteration(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "ab/c");
         assert_eq!(line.current(), '/');
     }
@@ -879,7 +1045,8 @@ mod tests {
     #[test]
     fn test_transliteration_with_escaped_backslash() {
         let (lines, mut line) = make_providers("/ab\\\\c/");
-        let parsed = parse_transliteration(&lines, &mut line).unwrap();
+        let parsed =
+            parse_transliteration(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "ab\\c");
         assert_eq!(line.current(), '/');
     }
@@ -887,7 +1054,8 @@ mod tests {
     #[test]
     fn test_transliteration_with_escape_sequence() {
         let (lines, mut line) = make_providers("/ab\\n/");
-        let parsed = parse_transliteration(&lines, &mut line).unwrap();
+        let parsed =
+            parse_transliteration(&lines, &mut line, &ProcessingContext::default()).unwrap();
         assert_eq!(parsed, "ab\n");
         assert_eq!(line.current(), '/');
     }
@@ -895,7 +1063,8 @@ mod tests {
     #[test]
     fn errors_on_unterminated_transliteration() {
         let (lines, mut line) = make_providers("/unterminated");
-        let err = parse_transliteration(&lines, &mut line).unwrap_err();
+        let err =
+            parse_transliteration(&lines, &mut line, &ProcessingContext::default()).unwrap_err();
         assert!(
             err.to_string()
                 .contains("unterminated transliteration string")
@@ -905,10 +1074,40 @@ mod tests {
     #[test]
     fn errors_on_esc_at_tr_eol() {
         let (lines, mut line) = make_providers("/foo\\");
-        let err = parse_transliteration(&lines, &mut line).unwrap_err();
+        let err =
+            parse_transliteration(&lines, &mut line, &ProcessingContext::default()).unwrap_err();
         assert!(
             err.to_string()
                 .contains("unterminated transliteration string")
         );
     }
+
+    // parse_script_escape
+    #[test]
+    fn test_script_escape_gnu() {
+        let (lines, mut line) = make_providers("x41");
+        let parsed = parse_script_escape(&lines, &mut line, &ProcessingContext::default());
+        assert_eq!(parsed.unwrap(), Some('A'));
+    }
+
+    #[test]
+    fn test_script_escape_posix() {
+        let context = ProcessingContext {
+            posix: true,
+            ..Default::default()
+        };
+
+        let (lines, mut line) = make_providers("t");
+        let parsed = parse_script_escape(&lines, &mut line, &context);
+        assert_eq!(parsed.unwrap(), Some('\t'));
+
+        for escape in ["cA", "d065", "o101", "u0041", "U00000041", "x41"] {
+            let (lines, mut line) = make_providers(escape);
+            let err = parse_script_escape(&lines, &mut line, &context).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("escape is not allowed with --posix")
+            );
+        }
+    }
 }
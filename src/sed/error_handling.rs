@@ -14,6 +14,7 @@ use crate::sed::script_line_provider::ScriptLineProvider;
 
 use std::rc::Rc;
 
+use uucore::display::Quotable;
 use uucore::error::{UResult, USimpleError};
 
 #[derive(Clone, Debug)]
@@ -64,6 +65,25 @@ pub fn compilation_error<T>(
     ))
 }
 
+/// Fail with a compile error at the provider location if the specified
+/// non-POSIX extension is used in strict POSIX (--posix) mode.
+pub fn posix_check(
+    lines: &ScriptLineProvider,
+    line: &ScriptCharProvider,
+    context: &ProcessingContext,
+    extension: impl ToString,
+) -> UResult<()> {
+    if context.posix {
+        compilation_error(
+            lines,
+            line,
+            format!("{} is not allowed with --posix", extension.to_string()),
+        )
+    } else {
+        Ok(())
+    }
+}
+
 /// Fail with msg as a compilation error at the command's location.
 /// The error's exit code is as specified.
 fn location_error<T>(location: &ScriptLocation, msg: impl ToString, exit_code: i32) -> UResult<T> {
@@ -108,7 +128,7 @@ pub fn input_runtime_error<T>(
             location.input_name,
             location.line_number,
             location.column_number,
-            context.input_name,
+            context.input_name.quote(),
             context.line_number,
             msg.to_string()
         ),
@@ -21,13 +21,13 @@ use memmap2::Mmap;
 
 use std::cell::Cell;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 
 #[cfg(not(unix))]
 use std::marker::PhantomData;
 
 #[cfg(unix)]
-use std::os::fd::RawFd;
+use std::os::fd::{AsFd, RawFd};
 
 #[cfg(unix)]
 use std::os::unix::io::AsRawFd;
@@ -54,6 +54,7 @@ pub struct MmapLineCursor<'a> {
     fast_copy: FastCopy, // Data for fast file copy I/O
     data: &'a [u8],      // Mmapped data
     pos: usize,          // Position within the data
+    separator: u8,       // Line separator: \n or \0 (-z)
 }
 
 #[cfg(unix)]
@@ -66,12 +67,13 @@ pub struct NextMmapLine<'a> {
 
 #[cfg(unix)]
 impl<'a> MmapLineCursor<'a> {
-    fn new(file: File, data: &'a [u8]) -> Self {
+    fn new(file: File, data: &'a [u8], separator: u8) -> Self {
         Self {
             fast_copy: FastCopy::new(&file),
             _file: file,
             data,
             pos: 0,
+            separator,
         }
     }
 
@@ -83,19 +85,19 @@ impl<'a> MmapLineCursor<'a> {
 
         let start = self.pos;
 
-        let mut end = if let Some(pos) = memchr(b'\n', &self.data[start..]) {
+        let mut end = if let Some(pos) = memchr(self.separator, &self.data[start..]) {
             pos + start
         } else {
             self.data.len()
         };
 
         if end < self.data.len() {
-            end += 1; // include \n in full span
+            end += 1; // include the separator in full span
         }
 
         self.pos = end;
         let full_span = &self.data[start..end];
-        let content = if full_span.ends_with(b"\n") {
+        let content = if full_span.last() == Some(&self.separator) {
             &full_span[..full_span.len() - 1]
         } else {
             full_span
@@ -113,40 +115,96 @@ impl<'a> MmapLineCursor<'a> {
 /// Buffered line reader from any BufRead input.
 pub struct ReadLineCursor {
     reader: Box<dyn BufRead>,
-    buffer: String,
+    buffer: Vec<u8>,
+    separator: u8,   // Line separator: \n or \0 (-z)
+    lookahead: bool, // Read ahead to identify the last line
 }
 
 impl ReadLineCursor {
-    /// Construct from anything that implements `Read`.
-    fn new<R: Read + 'static>(r: R) -> Self {
-        let buf = BufReader::new(r);
+    /// Construct from anything that implements `BufRead`.
+    fn new<R: BufRead + 'static>(r: R, separator: u8) -> Self {
         Self {
-            reader: Box::new(buf),
-            buffer: String::new(),
+            reader: Box::new(r),
+            buffer: Vec::new(),
+            separator,
+            lookahead: true,
         }
     }
 
-    /// If a line is available, return it, its \n termination,
+    /// If a line is available, return it, its separator termination,
     /// and next line availability, otherwise return None.
+    /// Without lookahead a line is never reported as the last one.
     fn get_line(&mut self) -> io::Result<Option<(String, bool, bool)>> {
         self.buffer.clear();
-        // read_line *includes* the '\n' if present
-        let bytes_read = self.reader.read_line(&mut self.buffer)?;
+        // read_until *includes* the separator if present
+        let bytes_read = self.reader.read_until(self.separator, &mut self.buffer)?;
         if bytes_read == 0 {
             return Ok(None);
         }
-        // O(1) check whether it ended in '\n'
-        let has_newline = self.buffer.ends_with('\n');
+        // O(1) check whether it ended in the separator
+        let has_newline = self.buffer.last() == Some(&self.separator);
         // strip it if you don’t want to expose it to the caller
         if has_newline {
             self.buffer.pop();
         }
-        let line = std::mem::take(&mut self.buffer);
-        let is_last_line = self.reader.fill_buf()?.is_empty();
+        let line = String::from_utf8(std::mem::take(&mut self.buffer))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let is_last_line = self.lookahead && self.reader.fill_buf()?.is_empty();
         Ok(Some((line, has_newline, is_last_line)))
     }
 }
 
+/// Reader that consumes no more input than what has been returned to
+/// its caller, so that other processes can read the remaining input (-u).
+/// Seekable input is read in blocks, and when the reader is dropped the
+/// file offset is moved back to the end of the consumed data.
+/// Other input, such as pipes, is read byte by byte.
+/// As such input can't be pushed back, when the script needs to detect
+/// the last line ($), the byte following each line is also consumed.
+struct MinimalReader {
+    reader: BufReader<File>,
+    seekable: bool,
+}
+
+impl MinimalReader {
+    fn new(mut file: File) -> Self {
+        let seekable = file.stream_position().is_ok();
+        let reader = if seekable {
+            BufReader::new(file)
+        } else {
+            BufReader::with_capacity(1, file)
+        };
+        Self { reader, seekable }
+    }
+}
+
+impl Read for MinimalReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl BufRead for MinimalReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt);
+    }
+}
+
+impl Drop for MinimalReader {
+    fn drop(&mut self) {
+        if self.seekable {
+            // Position the file after the consumed data.
+            // Errors can't be reported at this point.
+            let unconsumed = self.reader.buffer().len() as i64;
+            let _ = self.reader.get_mut().seek(SeekFrom::Current(-unconsumed));
+        }
+    }
+}
+
 /// A chunk of data that is input and can be output, often very efficiently
 #[derive(Debug, PartialEq, Eq)]
 pub struct IOChunk<'a> {
@@ -187,18 +245,14 @@ impl<'a> IOChunk<'a> {
         self.content.len() == 0
     }
 
-    /// Return true if the content ends with a newline.
+    /// Return true if the content ends with a line separator.
     pub fn is_newline_terminated(&self) -> bool {
         match &self.content {
             IOChunkContent::Owned { has_newline, .. } => *has_newline,
             #[cfg(unix)]
-            IOChunkContent::MmapInput { full_span, .. } => {
-                if let Some(&last) = full_span.last() {
-                    last == b'\n'
-                } else {
-                    false
-                }
-            }
+            IOChunkContent::MmapInput {
+                content, full_span, ..
+            } => full_span.len() > content.len(),
         }
     }
 
@@ -268,7 +322,7 @@ impl<'a> IOChunk<'a> {
                 content, full_span, ..
             } => match std::str::from_utf8(content) {
                 Ok(valid_str) => {
-                    let has_newline = full_span.last().copied() == Some(b'\n');
+                    let has_newline = full_span.len() > content.len();
                     self.content = IOChunkContent::new_owned(valid_str.to_string(), has_newline);
                     self.utf8_verified.set(true);
                     Ok(())
@@ -304,12 +358,12 @@ enum IOChunkContent<'a> {
     MmapInput {
         fast_copy: FastCopy, // Data for fast file copy I/O
         base: *const u8,     // Mmap start address
-        content: &'a [u8],   // Line without newline
-        full_span: &'a [u8], // Line including original newline, if any
+        content: &'a [u8],   // Line without separator
+        full_span: &'a [u8], // Line including original separator, if any
     },
     Owned {
-        content: String,   // Line content without newline
-        has_newline: bool, // True if \n-terminated
+        content: String,   // Line content without separator
+        has_newline: bool, // True if separator-terminated
         #[cfg(not(unix))]
         _phantom: PhantomData<&'a ()>, // Silence E0392 warning
     },
@@ -402,6 +456,47 @@ impl Default for FastCopy {
     }
 }
 
+/// File name that r and R commands use to read the standard input
+pub const STDIN_PATH: &str = "/dev/stdin";
+/// File name that w commands use to write to the standard output
+pub const STDOUT_PATH: &str = "/dev/stdout";
+/// File name that w commands use to write to the standard error
+pub const STDERR_PATH: &str = "/dev/stderr";
+
+/// Return a File sharing the standard input's offset.
+/// This works without the /dev/stdin device node and does not lock
+/// the standard input, which may also be the main input.
+pub fn stdin_file() -> io::Result<File> {
+    #[cfg(unix)]
+    {
+        Ok(File::from(io::stdin().as_fd().try_clone_to_owned()?))
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsHandle;
+        Ok(File::from(io::stdin().as_handle().try_clone_to_owned()?))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "reading the standard input as a file is not supported on this platform",
+        ))
+    }
+}
+
+/// Open the specified file named in a script for reading.
+/// The STDIN_PATH name reads the remaining standard input.
+pub fn open_script_input(path: &PathBuf) -> io::Result<File> {
+    if path.as_os_str() == STDIN_PATH {
+        stdin_file()
+    } else {
+        File::open(path)
+    }
+}
+
 /// Unified reader that uses mmap when possible, falls back to buffered reading.
 pub enum LineReader<'a> {
     #[cfg(unix)]
@@ -415,21 +510,53 @@ pub enum LineReader<'a> {
 }
 
 /// Return a LineReader that uses the ReadInput method fot the specified file.
-fn line_reader_read_input(file: File) -> io::Result<LineReader<'static>> {
+/// With minimal, read no more input than required (-u).
+fn line_reader_read_input(
+    file: File,
+    separator: u8,
+    minimal: bool,
+) -> io::Result<LineReader<'static>> {
+    if minimal {
+        return Ok(LineReader::ReadInput(ReadLineCursor::new(
+            MinimalReader::new(file),
+            separator,
+        )));
+    }
     let boxed: Box<dyn Read> = Box::new(file);
     let reader = BufReader::new(boxed);
-    Ok(LineReader::ReadInput(ReadLineCursor::new(reader)))
+    Ok(LineReader::ReadInput(ReadLineCursor::new(
+        reader, separator,
+    )))
 }
 
 impl<'a> LineReader<'a> {
-    /// Open the specified file for line input.
+    /// Open the specified file for input of lines ending in separator.
     // Use "-" to read from the standard input.
-    pub fn open(path: &PathBuf) -> io::Result<Self> {
+    pub fn open(path: &PathBuf, separator: u8) -> io::Result<Self> {
+        Self::open_input(path, separator, false)
+    }
+
+    /// Open the specified file for input of lines ending in separator,
+    /// reading no more input than required (-u).
+    // Use "-" to read from the standard input.
+    pub fn open_minimal(path: &PathBuf, separator: u8) -> io::Result<Self> {
+        Self::open_input(path, separator, true)
+    }
+
+    /// Open the specified file for input of lines ending in separator.
+    fn open_input(path: &PathBuf, separator: u8, minimal: bool) -> io::Result<Self> {
         if path.as_os_str() == "-" {
+            #[cfg(unix)]
+            if minimal {
+                return line_reader_read_input(stdin_file()?, separator, true);
+            }
+
             let stdin = io::stdin();
             let boxed: Box<dyn Read> = Box::new(stdin.lock());
             let reader = BufReader::new(boxed);
-            return Ok(LineReader::ReadInput(ReadLineCursor::new(reader)));
+            return Ok(LineReader::ReadInput(ReadLineCursor::new(
+                reader, separator,
+            )));
         }
 
         let file = File::open(path)?;
@@ -442,28 +569,37 @@ impl<'a> LineReader<'a> {
                     let slice: &'static [u8] = unsafe {
                         std::slice::from_raw_parts(mapped_file.as_ptr(), mapped_file.len())
                     };
-                    let cursor = MmapLineCursor::new(file, slice);
+                    let cursor = MmapLineCursor::new(file, slice, separator);
                     Ok(LineReader::MmapInput {
                         mapped_file,
                         cursor,
                     })
                 }
                 // Fallback to ReadInput
-                Err(_) => line_reader_read_input(file),
+                Err(_) => line_reader_read_input(file, separator, minimal),
             }
         }
 
         #[cfg(not(unix))]
         {
-            line_reader_read_input(file)
+            line_reader_read_input(file, separator, minimal)
         }
     }
 
     /// Open the specified file to read as a stream.
     #[cfg(test)]
-    pub fn open_stream(path: &PathBuf) -> io::Result<Self> {
+    pub fn open_stream(path: &PathBuf, separator: u8) -> io::Result<Self> {
         let file = File::open(path)?;
-        line_reader_read_input(file)
+        line_reader_read_input(file, separator, false)
+    }
+
+    /// Specify whether reading a line also determines whether it is the
+    /// last one.  Without this, streamed lines are never reported as last,
+    /// but they are returned as soon as they become available.
+    pub fn set_lookahead(&mut self, lookahead: bool) {
+        if let LineReader::ReadInput(cursor) = self {
+            cursor.lookahead = lookahead;
+        }
     }
 
     /// Return the next line, if available and also the availability
@@ -542,6 +678,7 @@ struct MmapOutput {
 /// All other output is buffered and writen via BufWriter.
 pub struct OutputBuffer {
     out: BufWriter<Box<dyn OutputWrite + 'static>>, // Where to write
+    separator: u8,                                  // Line separator: \n or \0 (-z)
     #[cfg(unix)]
     fast_copy: FastCopy,            // Data for fast file copy ops
     #[cfg(unix)]
@@ -580,6 +717,7 @@ impl OutputBuffer {
     pub fn new(w: Box<dyn OutputWrite + 'static>) -> Self {
         Self {
             out: BufWriter::new(w),
+            separator: b'\n',
             #[cfg(test)]
             low_level_flushes: 0,
         }
@@ -595,6 +733,7 @@ impl OutputBuffer {
         };
         Self {
             out: BufWriter::new(w),
+            separator: b'\n',
             fast_copy,
             max_pending_write,
             mmap_chunk: None,
@@ -603,6 +742,11 @@ impl OutputBuffer {
         }
     }
 
+    /// Set the separator used to terminate output lines.
+    pub fn set_separator(&mut self, separator: u8) {
+        self.separator = separator;
+    }
+
     /// Schedule the specified String or &strfor eventual output
     pub fn write_str<S: Into<String>>(&mut self, s: S) -> io::Result<()> {
         self.write_chunk(&IOChunk::from_content(IOChunkContent::new_owned(
@@ -619,7 +763,7 @@ impl OutputBuffer {
             self.flush_mmap(WriteRange::Complete)?;
         }
 
-        let file = match File::open(path) {
+        let file = match open_script_input(path) {
             Ok(f) => f,
             // Per POSIX, if the file can't be read treat it as empty.
             Err(_) => return Ok(()),
@@ -712,7 +856,7 @@ impl OutputBuffer {
                 self.flush_mmap(WriteRange::Complete)?;
                 self.out.write_all(content.as_bytes())?;
                 if *has_newline {
-                    self.out.write_all(b"\n")?;
+                    self.out.write_all(&[self.separator])?;
                 }
             }
         }
@@ -781,7 +925,7 @@ impl OutputBuffer {
             } => {
                 self.out.write_all(content.as_bytes())?;
                 if *has_newline {
-                    self.out.write_all(b"\n")?;
+                    self.out.write_all(&[self.separator])?;
                 }
                 Ok(())
             }
@@ -960,7 +1104,7 @@ pub fn main() -> io::Result<()> {
         .nth(1)
         .map(PathBuf::from)
         .unwrap_or_else(|| "-".into());
-    let mut reader = LineReader::open(&path)?;
+    let mut reader = LineReader::open(&path, b'\n')?;
     let stdout = Box::new(io::stdout().lock());
     let mut output = OutputBuffer::new(stdout);
 
@@ -1039,7 +1183,7 @@ mod tests {
         let input_path = input.path().to_path_buf();
 
         // Open the reader on that file
-        let mut reader = LineReader::open(&input_path)?;
+        let mut reader = LineReader::open(&input_path, b'\n')?;
 
         // Prepare an output temp file and wrap it in our OutputBuffer
         let output = NamedTempFile::new()?;
@@ -1075,7 +1219,7 @@ mod tests {
         input.write_all(data)?;
         input.flush()?;
         let input_path = input.path().to_path_buf();
-        let mut reader = LineReader::open(&input_path)?;
+        let mut reader = LineReader::open(&input_path, b'\n')?;
 
         // Prepare an empty output file
         let output = NamedTempFile::new()?;
@@ -1127,7 +1271,7 @@ mod tests {
         let input_path = input.path().to_path_buf();
 
         // Open reader on input file:
-        let mut reader = LineReader::open(&input_path)?;
+        let mut reader = LineReader::open(&input_path, b'\n')?;
 
         // Create the output temp file (empty):
         let output = NamedTempFile::new()?;
@@ -1166,7 +1310,7 @@ mod tests {
         let input_path = input.path().to_path_buf();
 
         // Open reader on input file:
-        let mut reader = LineReader::open(&input_path)?;
+        let mut reader = LineReader::open(&input_path, b'\n')?;
 
         // Create the output temp file (empty):
         let output = NamedTempFile::new()?;
@@ -1201,7 +1345,7 @@ mod tests {
         let input_path = input.path().to_path_buf();
 
         // Open reader on input file:
-        let mut reader = LineReader::open(&input_path)?;
+        let mut reader = LineReader::open(&input_path, b'\n')?;
 
         // Create the output temp file (empty):
         let output = NamedTempFile::new()?;
@@ -1236,7 +1380,7 @@ mod tests {
         let input_path = input.path().to_path_buf();
 
         // Open reader on input file:
-        let mut reader = LineReader::open_stream(&input_path)?;
+        let mut reader = LineReader::open_stream(&input_path, b'\n')?;
 
         // Create the output temp file (empty):
         let output = NamedTempFile::new()?;
@@ -1278,7 +1422,7 @@ mod tests {
         let input_path = input.path().to_path_buf();
 
         // Open reader on input file:
-        let mut reader = LineReader::open(&input_path)?;
+        let mut reader = LineReader::open(&input_path, b'\n')?;
 
         // Create the output temp file (empty):
         let output = NamedTempFile::new()?;
@@ -1316,7 +1460,7 @@ mod tests {
         tmp.flush()?;
 
         let path = tmp.path().to_path_buf();
-        let mut reader = LineReader::open_stream(&path)?;
+        let mut reader = LineReader::open_stream(&path, b'\n')?;
 
         // Verify the reader's operation
         if let Some((
@@ -1383,7 +1527,7 @@ mod tests {
         tmp.flush()?;
 
         let path = tmp.path().to_path_buf();
-        let mut reader = LineReader::open(&path)?;
+        let mut reader = LineReader::open(&path, b'\n')?;
 
         // Verify the reader's operation
         if let Some((
@@ -1443,6 +1587,137 @@ mod tests {
         Ok(())
     }
 
+    /// Read all lines of a file containing NUL-separated data.
+    fn read_null_separated(stream: bool) -> std::io::Result<Vec<(String, bool, bool)>> {
+        let mut tmp = NamedTempFile::new()?;
+        tmp.write_all(b"first\nline\0second\0last")?;
+        tmp.flush()?;
+
+        let path = tmp.path().to_path_buf();
+        let mut reader = if stream {
+            LineReader::open_stream(&path, b'\0')?
+        } else {
+            LineReader::open(&path, b'\0')?
+        };
+
+        let mut result = Vec::new();
+        while let Some((chunk, last_line)) = reader.get_line()? {
+            result.push((
+                chunk.as_str().unwrap().to_string(),
+                chunk.is_newline_terminated(),
+                last_line,
+            ));
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn test_null_separated_read() -> std::io::Result<()> {
+        for stream in [false, true] {
+            assert_eq!(
+                read_null_separated(stream)?,
+                vec![
+                    ("first\nline".to_string(), true, false),
+                    ("second".to_string(), true, false),
+                    ("last".to_string(), false, true),
+                ]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_minimal_read_seekable() -> std::io::Result<()> {
+        let mut file = tempfile()?;
+        file.write_all(b"one\ntwo\nthree\n")?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut reader = line_reader_read_input(file.try_clone()?, b'\n', true)?;
+        let (chunk, last_line) = reader.get_line()?.unwrap();
+        assert_eq!(chunk.as_str().unwrap(), "one");
+        assert!(!last_line);
+        drop(reader);
+
+        // The file offset is moved back after the consumed line.
+        assert_eq!(file.stream_position()?, 4);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_minimal_read_pipe() -> std::io::Result<()> {
+        let (pipe_reader, mut pipe_writer) = io::pipe()?;
+        pipe_writer.write_all(b"one\ntwo\n")?;
+        drop(pipe_writer);
+        let mut rest_reader = pipe_reader.try_clone()?;
+
+        let file = File::from(std::os::fd::OwnedFd::from(pipe_reader));
+        let mut reader = line_reader_read_input(file, b'\n', true)?;
+        let (chunk, last_line) = reader.get_line()?.unwrap();
+        assert_eq!(chunk.as_str().unwrap(), "one");
+        assert!(!last_line);
+        drop(reader);
+
+        // The byte needed to check for the last line is consumed.
+        let mut rest = String::new();
+        rest_reader.read_to_string(&mut rest)?;
+        assert_eq!(rest, "wo\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_minimal_read_pipe_without_lookahead() -> std::io::Result<()> {
+        let (pipe_reader, mut pipe_writer) = io::pipe()?;
+        pipe_writer.write_all(b"one\ntwo\n")?;
+        drop(pipe_writer);
+        let mut rest_reader = pipe_reader.try_clone()?;
+
+        let file = File::from(std::os::fd::OwnedFd::from(pipe_reader));
+        let mut reader = line_reader_read_input(file, b'\n', true)?;
+        reader.set_lookahead(false);
+        let (chunk, last_line) = reader.get_line()?.unwrap();
+        assert_eq!(chunk.as_str().unwrap(), "one");
+        assert!(!last_line);
+        drop(reader);
+
+        let mut rest = String::new();
+        rest_reader.read_to_string(&mut rest)?;
+        assert_eq!(rest, "two\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_without_lookahead() -> std::io::Result<()> {
+        let mut tmp = NamedTempFile::new()?;
+        tmp.write_all(b"one\ntwo\n")?;
+        tmp.flush()?;
+
+        let mut reader = LineReader::open_stream(&tmp.path().to_path_buf(), b'\n')?;
+        reader.set_lookahead(false);
+        let mut last_lines = Vec::new();
+        while let Some((_chunk, last_line)) = reader.get_line()? {
+            last_lines.push(last_line);
+        }
+        assert_eq!(last_lines, vec![false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn owned_with_null_separator() {
+        let (mut buf, mut file) = new_for_test();
+        buf.set_separator(b'\0');
+        let chunk = make_owned_chunk("world", true);
+        buf.write_chunk(&chunk).unwrap();
+
+        buf.out.flush().unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut out = String::new();
+        file.read_to_string(&mut out).unwrap();
+
+        assert_eq!(out, "world\0");
+    }
+
     // is_newline_terminated, is_empty
     #[test]
     fn test_owned_newline_terminated_non_empty() {
@@ -1790,6 +2065,7 @@ mod tests {
         let file = tempfile().unwrap();
         let buf = OutputBuffer {
             out: BufWriter::new(Box::new(file.try_clone().unwrap())),
+            separator: b'\n',
             #[cfg(unix)]
             fast_copy: FastCopy {
                 fd: -1,
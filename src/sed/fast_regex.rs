@@ -14,13 +14,15 @@
 use fancy_regex::{
     CaptureMatches as FancyCaptureMatches, Captures as FancyCaptures, Regex as FancyRegex,
 };
-use memchr::memmem;
+use memchr::{memchr_iter, memmem};
 use once_cell::sync::Lazy;
 use regex::Regex as RustRegex;
 use regex::bytes::{
     CaptureMatches as ByteCaptureMatches, Captures as ByteCaptures, Regex as ByteRegex,
+    RegexBuilder as ByteRegexBuilder,
 };
 use std::error::Error;
+use std::iter;
 use uucore::error::{UResult, USimpleError};
 
 use crate::sed::fast_io::IOChunk;
@@ -56,7 +58,7 @@ static NEEDS_FANCY_RE: Lazy<RustRegex> = Lazy::new(|| {
               \.                  # . matches any Unicode character
               | \[\^              # Bracketed -ve character class
               | \(\?i             # (Unicode) case insensitive
-              | \\[WwDdSsBbPp]    # Unicode classes
+              | \\[WwDdSsBbPp<>]  # Unicode classes and word boundaries
               | \\[0-9]           # Back-references need fancy
             )
           )
@@ -87,7 +89,7 @@ static NEEDS_RE: Lazy<RustRegex> = Lazy::new(|| {
                                  # BOL but they should error there,
                                  # not use them as literals.
              | \\[WwDdSsPp]      # Unicode classes
-             | \\[AzBb]          # Empty matches
+             | \\[AzBb<>]        # Empty matches
              | \\[0-9]           # Back-references
            )
         ",
@@ -109,6 +111,7 @@ enum AnchoredMatch {
 pub struct LiteralMatcher {
     needle: Vec<u8>,           // Bytes without any anchors
     match_type: AnchoredMatch, // Type of anchoring specified
+    multiline: bool,           // True if anchors also match at newlines (M)
 }
 
 impl LiteralMatcher {
@@ -118,21 +121,25 @@ impl LiteralMatcher {
         if needle_bytes[0] == b'^' && needle_bytes[needle_bytes.len() - 1] == b'$' {
             LiteralMatcher {
                 match_type: AnchoredMatch::Both,
+                multiline: false,
                 needle: needle_bytes[1..needle_bytes.len() - 1].to_vec(),
             }
         } else if needle_bytes[0] == b'^' {
             LiteralMatcher {
                 match_type: AnchoredMatch::Begin,
+                multiline: false,
                 needle: needle_bytes[1..needle_bytes.len()].to_vec(),
             }
         } else if needle_bytes[needle_bytes.len() - 1] == b'$' {
             LiteralMatcher {
                 match_type: AnchoredMatch::End,
+                multiline: false,
                 needle: needle_bytes[0..needle_bytes.len() - 1].to_vec(),
             }
         } else {
             LiteralMatcher {
                 match_type: AnchoredMatch::Free,
+                multiline: false,
                 needle: needle_bytes.to_vec(),
             }
         }
@@ -140,6 +147,10 @@ impl LiteralMatcher {
 
     /// Returns the start index of a match, if any
     fn anchored_find(&self, haystack: &[u8]) -> Option<usize> {
+        if self.multiline {
+            return self.multiline_find_iter(haystack).next();
+        }
+
         let nlen = self.needle.len();
         let hlen = haystack.len();
 
@@ -169,6 +180,35 @@ impl LiteralMatcher {
         }
     }
 
+    /// Return the start indices of all matches, treating ^ and $ as
+    /// line anchors that also match after and before embedded newlines.
+    fn multiline_find_iter<'t>(
+        &'t self,
+        haystack: &'t [u8],
+    ) -> Box<dyn Iterator<Item = usize> + 't> {
+        let needle = self.needle.as_slice();
+        let nlen = needle.len();
+        let hlen = haystack.len();
+        let line_starts = iter::once(0).chain(memchr_iter(b'\n', haystack).map(|i| i + 1));
+        let line_ends = memchr_iter(b'\n', haystack).chain(iter::once(hlen));
+
+        match self.match_type {
+            AnchoredMatch::Both => Box::new(line_starts.filter(move |&start| {
+                let end = start + nlen;
+                haystack[start..].starts_with(needle) && (end == hlen || haystack[end] == b'\n')
+            })),
+            AnchoredMatch::Begin => {
+                Box::new(line_starts.filter(move |&start| haystack[start..].starts_with(needle)))
+            }
+            AnchoredMatch::End => Box::new(
+                line_ends
+                    .filter(move |&end| haystack[..end].ends_with(needle))
+                    .map(move |end| end - nlen),
+            ),
+            AnchoredMatch::Free => Box::new(memmem::find_iter(haystack, needle)),
+        }
+    }
+
     /// Return true if the needle occurs in the haystack.
     pub fn is_match(&self, haystack: &[u8]) -> bool {
         self.anchored_find(haystack).is_some()
@@ -189,24 +229,21 @@ impl LiteralMatcher {
         &'t self,
         haystack: &'t [u8],
     ) -> Box<dyn Iterator<Item = (usize, usize, &'t str)> + 't> {
-        let needle = &self.needle;
-        let nlen = needle.len();
+        let nlen = self.needle.len();
 
         match self.match_type {
-            AnchoredMatch::Both | AnchoredMatch::Begin | AnchoredMatch::End => {
+            AnchoredMatch::Both | AnchoredMatch::Begin | AnchoredMatch::End if !self.multiline => {
                 // At most one match; yield it if present
                 Box::new(self.find(haystack).into_iter())
             }
-            AnchoredMatch::Free => {
+            _ => {
                 // Multiple potential matches
-                Box::new(
-                    memmem::find_iter(haystack, needle).filter_map(move |start| {
-                        let end = start + nlen;
-                        std::str::from_utf8(&haystack[start..end])
-                            .ok()
-                            .map(|s| (start, end, s))
-                    }),
-                )
+                Box::new(self.multiline_find_iter(haystack).filter_map(move |start| {
+                    let end = start + nlen;
+                    std::str::from_utf8(&haystack[start..end])
+                        .ok()
+                        .map(|s| (start, end, s))
+                }))
             }
         }
     }
@@ -243,12 +280,30 @@ pub enum Regex {
 impl Regex {
     /// Construct the most efficient RE-like matching engine possible.
     pub fn new(pattern: &str) -> Result<Self, Box<dyn Error>> {
+        Self::with_multiline(pattern, false)
+    }
+
+    /// Construct the most efficient RE-like matching engine possible.
+    /// As in sed, . matches a newline, except in multiline mode,
+    /// where ^ and $ instead also match at embedded newlines.
+    pub fn with_multiline(pattern: &str, multiline: bool) -> Result<Self, Box<dyn Error>> {
         if NEEDS_FANCY_RE.is_match(pattern) {
-            Ok(Self::Fancy(FancyRegex::new(pattern)?))
+            // Specify the mode inline rather than through a builder,
+            // because fancy_regex delegates \A and \z to the regex crate
+            // as ^ and $, which the builder's option turns into line anchors.
+            let flags = if multiline { "(?m)" } else { "(?s)" };
+            Ok(Self::Fancy(FancyRegex::new(&format!("{flags}{pattern}"))?))
         } else if NEEDS_RE.is_match(pattern) {
-            Ok(Self::Byte(ByteRegex::new(pattern)?))
+            Ok(Self::Byte(
+                ByteRegexBuilder::new(pattern)
+                    .multi_line(multiline)
+                    .dot_matches_new_line(!multiline)
+                    .build()?,
+            ))
         } else {
-            Ok(Self::Literal(LiteralMatcher::new(&remove_escapes(pattern))))
+            let mut matcher = LiteralMatcher::new(&remove_escapes(pattern));
+            matcher.multiline = multiline;
+            Ok(Self::Literal(matcher))
         }
     }
 
@@ -486,6 +541,10 @@ mod tests {
             r"x\W",     // \W is Unicode-aware.
             r"x\S+",    // \S is Unicode-aware.
             r"x\d",     // \d includes all Unicode digits.
+            // Unicode word boundaries
+            r"\<x",
+            r"x\>",
+            r"x\b{end}",
             // .
             r".",
             r"x.",
@@ -553,6 +612,8 @@ mod tests {
             r"\S",      // Class shorthand
             r"\1",      // Backreference
             r"a\Pb",    // Unicode property
+            r"\<abc\>", // Word start and end
+            r"\Aabc\z", // Buffer start and end
         ];
 
         for pat in &should_match {
@@ -573,7 +634,8 @@ mod tests {
             r"^abc$",  // Anchors alone
             r"file\.", // Escaped dot
             r"literal123",
-            r"\\", // Escaped backslash
+            r"\\",  // Escaped backslash
+            r"\\<", // Escaped backslash before <
         ];
 
         for pat in &should_not_match {
@@ -604,6 +666,41 @@ mod tests {
         assert!(matches!(re, Regex::Literal(_)));
     }
 
+    #[test]
+    fn assert_multiline_buffer_anchors() {
+        let chunk = IOChunk::new_from_str("ab\ncd");
+        for pattern in [r"\Ac|d\z", r"\A.|.\z"] {
+            let re = Regex::with_multiline(pattern, true).unwrap();
+            let matches: Vec<_> = re
+                .captures_iter(&chunk)
+                .unwrap()
+                .map(|caps| caps.unwrap().get(0).unwrap().unwrap().as_str())
+                .collect();
+            assert_eq!(matches.last(), Some(&"d"), "pattern {pattern:?}");
+            assert_eq!(matches.len(), if pattern.contains('.') { 2 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn assert_dot_matches_newline_unless_multiline() {
+        let chunk = IOChunk::new_from_str("a\nb");
+        // Byte and Fancy engines
+        for pattern in [r"a.b", r"a.b\z"] {
+            let re = Regex::new(pattern).unwrap();
+            assert!(re.find(&chunk).unwrap().is_some(), "pattern {pattern:?}");
+            let re = Regex::with_multiline(pattern, true).unwrap();
+            assert!(re.find(&chunk).unwrap().is_none(), "pattern {pattern:?}");
+        }
+    }
+
+    #[test]
+    fn assert_word_anchors_not_literal() {
+        for pattern in [r"\<ab", r"ab\>", r"a\bb"] {
+            let re = Regex::new(pattern).unwrap();
+            assert!(!matches!(re, Regex::Literal(_)), "pattern {pattern:?}");
+        }
+    }
+
     #[test]
     fn handles_invalid_regex_gracefully() {
         let err = Regex::new("(").unwrap_err().to_string();
@@ -725,6 +822,37 @@ mod tests {
         assert_eq!(strings, ["test"]);
     }
 
+    fn multiline_matcher(needle: &str) -> LiteralMatcher {
+        let mut matcher = LiteralMatcher::new(needle);
+        matcher.multiline = true;
+        matcher
+    }
+
+    #[test]
+    fn test_literal_matcher_multiline_begin() {
+        let matcher = multiline_matcher("^ab");
+        let haystack = b"xab\nab\nabc";
+        assert_eq!(matcher.find(haystack).map(|m| m.0), Some(4));
+        let starts: Vec<_> = matcher.iter(haystack).map(|m| m.0).collect();
+        assert_eq!(starts, [4, 7]);
+    }
+
+    #[test]
+    fn test_literal_matcher_multiline_end() {
+        let matcher = multiline_matcher("aa$");
+        let haystack = b"aaa\nbaax\naa";
+        let starts: Vec<_> = matcher.iter(haystack).map(|m| m.0).collect();
+        assert_eq!(starts, [1, 9]);
+    }
+
+    #[test]
+    fn test_literal_matcher_multiline_both() {
+        let matcher = multiline_matcher("^ab$");
+        assert!(matcher.is_match(b"x\nab\ny"));
+        assert!(matcher.is_match(b"ab\n"));
+        assert!(!matcher.is_match(b"abc\nxab"));
+    }
+
     #[test]
     fn test_literal_matcher_no_match() {
         let matcher = LiteralMatcher::new("missing");
@@ -32,6 +32,7 @@ pub struct InPlace {
     pub follow_symlinks: bool,
     pub temp_file: Option<NamedTempFile>,
     pub original_path: Option<PathBuf>,
+    pub separator: u8,
 }
 
 impl InPlace {
@@ -39,6 +40,7 @@ impl InPlace {
     /// Depending on its settings it may or may not perform in-place
     /// editing, backup the original file, or follow symlinks.
     pub fn new(context: ProcessingContext) -> Self {
+        let separator = context.separator() as u8;
         Self {
             output: OutputBuffer::new(Box::new(stdout())),
             in_place: context.in_place,
@@ -46,6 +48,7 @@ impl InPlace {
             follow_symlinks: context.follow_symlinks,
             temp_file: None,
             original_path: None,
+            separator,
         }
     }
 
@@ -68,6 +71,7 @@ impl InPlace {
     fn begin_resolved(&mut self, file_name: &Path) -> UResult<&mut OutputBuffer> {
         if !self.in_place {
             self.output = OutputBuffer::new(Box::new(stdout()));
+            self.output.set_separator(self.separator);
             return Ok(&mut self.output);
         }
 
@@ -102,9 +106,10 @@ impl InPlace {
             fs::set_permissions(temp_file.path(), perms)?;
         }
 
-        let output = OutputBuffer::new(Box::new(
+        let mut output = OutputBuffer::new(Box::new(
             temp_file.reopen().expect("reopening NamedTempFile"),
         ));
+        output.set_separator(self.separator);
         self.output = output;
         self.temp_file = Some(temp_file);
         self.original_path = Some(file_name.to_path_buf());
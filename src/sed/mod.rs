@@ -10,11 +10,13 @@
 
 pub mod command;
 pub mod compiler;
+pub mod debug;
 pub mod delimited_parser;
 pub mod error_handling;
 pub mod fast_io;
 pub mod fast_regex;
 pub mod in_place;
+pub mod named_reader;
 pub mod named_writer;
 pub mod processor;
 pub mod script_char_provider;
@@ -199,16 +201,18 @@ fn build_context(matches: &ArgMatches) -> ProcessingContext {
             .unwrap_or(70),
         quiet: matches.get_flag("quiet"),
         posix: matches.get_flag("posix"),
-        separate: matches.get_flag("separate"),
+        // In-place editing treats each file as a separate stream.
+        separate: matches.get_flag("separate") || matches.contains_id("in-place"),
         sandbox: matches.get_flag("sandbox"),
         unbuffered: matches.get_flag("unbuffered"),
         null_data: matches.get_flag("null-data"),
 
         // Other context
-        input_name: "<stdin>".to_string(),
+        input_name: "-".to_string(),
         line_number: 0,
         last_address: false,
         last_line: false,
+        needs_last_line: false,
         last_file: false,
         stop_processing: false,
         saved_regex: None,
@@ -386,6 +390,7 @@ mod tests {
 
         assert!(ctx.in_place);
         assert_eq!(ctx.in_place_suffix, Some(".bak".to_string()));
+        assert!(ctx.separate);
     }
 
     #[test]
@@ -0,0 +1,146 @@
+// An abstraction for input files read a line at a time by the R command
+//
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Diomidis Spinellis
+//
+// This file is part of the uutils sed package.
+// It is licensed under the MIT License.
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+use crate::sed::error_handling::{ScriptLocation, runtime_error};
+use crate::sed::fast_io::open_script_input;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use uucore::display::Quotable;
+use uucore::error::UResult;
+
+thread_local! {
+    /// Global map of all readers, so that commands naming the same
+    /// file share its reading position
+    static READERS: RefCell<HashMap<PathBuf, Rc<RefCell<NamedReader>>>> =
+        RefCell::new(HashMap::new());
+}
+
+#[derive(Debug)]
+/// Reader that keeps its position in a file across execution cycles
+pub struct NamedReader {
+    pub path: PathBuf,
+    reader: Option<BufReader<File>>, // None until first read or at EOF
+    eof: bool,                       // True after EOF or a failed open
+    location: ScriptLocation,
+    separator: u8, // Line separator: \n or \0 (-z)
+}
+
+impl NamedReader {
+    /// Return the reader associated with path, creating it if needed.
+    /// The file is opened on the first read from it.
+    pub fn new(path: PathBuf, location: ScriptLocation, separator: char) -> Rc<RefCell<Self>> {
+        READERS.with(|map| {
+            map.borrow_mut()
+                .entry(path.clone())
+                .or_insert_with(|| {
+                    Rc::new(RefCell::new(NamedReader {
+                        path,
+                        reader: None,
+                        eof: false,
+                        location,
+                        separator: separator as u8,
+                    }))
+                })
+                .clone()
+        })
+    }
+
+    /// Return the file's next line without its separator, or None at EOF.
+    /// As with the r command, a file that cannot be read is silently
+    /// treated as empty.
+    pub fn read_line(&mut self) -> UResult<Option<String>> {
+        if self.eof {
+            return Ok(None);
+        }
+        if self.reader.is_none() {
+            match open_script_input(&self.path) {
+                Ok(file) => self.reader = Some(BufReader::new(file)),
+                Err(_) => {
+                    self.eof = true;
+                    return Ok(None);
+                }
+            }
+        }
+
+        let mut buf = Vec::new();
+        let reader = self.reader.as_mut().unwrap();
+        let n = reader.read_until(self.separator, &mut buf).map_err(|e| {
+            runtime_error::<()>(
+                &self.location,
+                format!("reading file {}: {e}", self.path.quote()),
+            )
+            .unwrap_err()
+        })?;
+        if n == 0 {
+            self.eof = true;
+            self.reader = None;
+            return Ok(None);
+        }
+        if buf.last() == Some(&self.separator) {
+            buf.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_read_lines_until_eof() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"one\ntwo").unwrap();
+        let reader = NamedReader::new(file.path().to_path_buf(), ScriptLocation::default(), '\n');
+
+        let mut reader = reader.borrow_mut();
+        assert_eq!(reader.read_line().unwrap().as_deref(), Some("one"));
+        assert_eq!(reader.read_line().unwrap().as_deref(), Some("two"));
+        assert_eq!(reader.read_line().unwrap(), None);
+        assert_eq!(reader.read_line().unwrap(), None);
+    }
+
+    #[test]
+    fn test_same_path_shares_reader() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"one\ntwo\n").unwrap();
+        let path = file.path().to_path_buf();
+        let first = NamedReader::new(path.clone(), ScriptLocation::default(), '\n');
+        let second = NamedReader::new(path, ScriptLocation::default(), '\n');
+
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(
+            first.borrow_mut().read_line().unwrap().as_deref(),
+            Some("one")
+        );
+        assert_eq!(
+            second.borrow_mut().read_line().unwrap().as_deref(),
+            Some("two")
+        );
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let reader = NamedReader::new(
+            PathBuf::from("/nonexistent/sed-named-reader"),
+            ScriptLocation::default(),
+            '\n',
+        );
+        assert_eq!(reader.borrow_mut().read_line().unwrap(), None);
+    }
+}
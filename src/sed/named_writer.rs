@@ -1,4 +1,4 @@
-// An abstraction for output files created on entry and flushed on exit
+// An abstraction for output files created on demand and flushed on exit
 //
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2025 Diomidis Spinellis
@@ -8,11 +8,14 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+use crate::sed::command::ProcessingContext;
 use crate::sed::error_handling::{ScriptLocation, runtime_error};
+use crate::sed::fast_io::{OutputBuffer, STDERR_PATH, STDOUT_PATH};
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
+use std::io::{self, BufWriter, Write};
 use std::path::PathBuf;
 use std::rc::Rc;
 
@@ -20,55 +23,132 @@ use uucore::display::Quotable;
 use uucore::error::UResult;
 
 thread_local! {
-    /// Global list of all writers that should be flushed at shutdown
-    static FLUSH_LIST: RefCell<Vec<Rc<RefCell<NamedWriter>>>> = const { RefCell::new(Vec::new()) };
+    /// Global map of all writers, so that commands naming the same
+    /// file share it, and so that they can be flushed at shutdown
+    static WRITERS: RefCell<HashMap<PathBuf, Rc<RefCell<NamedWriter>>>> =
+        RefCell::new(HashMap::new());
+}
+
+#[derive(Debug)]
+/// Destination of a writer's output
+enum Target {
+    File(Option<BufWriter<File>>), // None until the file is created
+    Output,                        // The main output buffer (/dev/stdout)
+    Stdout,                        // The standard output, bypassing -i files
+    Stderr,                        // The standard error (/dev/stderr)
 }
 
 #[derive(Debug)]
 /// Writer that tracks its file name for better error messages
 pub struct NamedWriter {
     pub path: PathBuf,
-    writer: BufWriter<File>,
-    location: ScriptLocation,
+    target: Target,
+    location: ScriptLocation, // The first command naming the file
+    separator: char,          // Line separator: \n or \0 (-z)
 }
 
 impl NamedWriter {
-    /// Create a new writer, truncate the file, and register it for flushing.
-    pub fn new(path: PathBuf, location: ScriptLocation) -> UResult<Rc<RefCell<Self>>> {
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&path)
-            .map_err(|e| {
-                runtime_error::<()>(&location, format!("creating file {}: {}", path.quote(), e))
-                    .unwrap_err()
-            })?;
+    /// Return the writer associated with path, creating and registering
+    /// it for flushing if needed.
+    /// The file is created or truncated now if -a is specified,
+    /// otherwise on the first write to it.
+    /// The special files /dev/stdout and /dev/stderr are never opened.
+    pub fn new(
+        path: PathBuf,
+        location: ScriptLocation,
+        context: &ProcessingContext,
+    ) -> UResult<Rc<RefCell<Self>>> {
+        if let Some(writer) = WRITERS.with(|map| map.borrow().get(&path).cloned()) {
+            return Ok(writer);
+        }
 
-        let writer = Rc::new(RefCell::new(NamedWriter {
-            path,
-            writer: BufWriter::new(file),
-            location,
-        }));
+        let target = if path.as_os_str() == STDOUT_PATH {
+            // Files edited in place are not the standard output.
+            if context.in_place {
+                Target::Stdout
+            } else {
+                Target::Output
+            }
+        } else if path.as_os_str() == STDERR_PATH {
+            Target::Stderr
+        } else {
+            Target::File(None)
+        };
+        let mut named_writer = NamedWriter {
+            path: path.clone(),
+            target,
+            location: location.clone(),
+            separator: context.separator(),
+        };
+        if context.all_output_files && matches!(named_writer.target, Target::File(_)) {
+            named_writer.create(&location)?;
+        }
 
-        FLUSH_LIST.with(|list| list.borrow_mut().push(Rc::clone(&writer)));
+        let writer = Rc::new(RefCell::new(named_writer));
+        WRITERS.with(|map| map.borrow_mut().insert(path, Rc::clone(&writer)));
         Ok(writer)
     }
 
-    /// Write a line to the file with a newline, returning descriptive errors.
-    pub fn write_line(&mut self, line: &str) -> UResult<()> {
-        writeln!(self.writer, "{line}").map_err(|e| {
+    /// Create or truncate the file, returning its writer.
+    /// Errors are reported at the specified command's location.
+    fn create(&mut self, location: &ScriptLocation) -> UResult<&mut BufWriter<File>> {
+        let Target::File(writer) = &mut self.target else {
+            unreachable!("only files are created");
+        };
+        if writer.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)
+                .map_err(|e| {
+                    runtime_error::<()>(
+                        location,
+                        format!("creating file {}: {}", self.path.quote(), e),
+                    )
+                    .unwrap_err()
+                })?;
+            *writer = Some(BufWriter::new(file));
+        }
+        Ok(writer.as_mut().unwrap())
+    }
+
+    /// Write a line to the file with a separator, returning descriptive
+    /// errors located at the writing command's location.
+    /// Lines written to /dev/stdout go to output, in order with the
+    /// rest of the output.
+    pub fn write_line(
+        &mut self,
+        output: &mut OutputBuffer,
+        line: &str,
+        location: &ScriptLocation,
+    ) -> UResult<()> {
+        let separator = self.separator;
+        let result = match self.target {
+            Target::File(_) => write!(self.create(location)?, "{line}{separator}"),
+            Target::Output => output.write_str(format!("{line}{separator}")),
+            Target::Stdout => write!(io::stdout().lock(), "{line}{separator}"),
+            Target::Stderr => write!(io::stderr().lock(), "{line}{separator}"),
+        };
+        result.map_err(|e| {
             runtime_error::<()>(
-                &self.location,
+                location,
                 format!("writing to file {}: {e}", self.path.quote()),
             )
             .unwrap_err()
         })
     }
 
-    /// Flush the writer, returning a descriptive error.
+    /// Flush the writer, returning a descriptive error located at the
+    /// first command naming the file.
     pub fn flush(&mut self) -> UResult<()> {
-        self.writer.flush().map_err(|e| {
+        let result = match &mut self.target {
+            Target::File(Some(writer)) => writer.flush(),
+            Target::Stdout => io::stdout().flush(),
+            // Unopened files, the output buffer, and unbuffered stderr
+            Target::File(None) | Target::Output | Target::Stderr => Ok(()),
+        };
+        result.map_err(|e| {
             runtime_error::<()>(
                 &self.location,
                 format!("writing to file {}: {}", self.path.quote(), e),
@@ -80,8 +160,8 @@ impl NamedWriter {
 
 /// Flush buffered content to the file, returning descriptive errors.
 pub fn flush_all() -> UResult<()> {
-    FLUSH_LIST.with(|cell| {
-        for handle in cell.borrow().iter() {
+    WRITERS.with(|cell| {
+        for handle in cell.borrow().values() {
             handle.borrow_mut().flush()?;
         }
 
@@ -12,7 +12,8 @@ use crate::sed::command::{
     Address, AddressType, AddressValue, AppendElement, Command, CommandData, InputAction,
     ProcessingContext, Transliteration,
 };
-use crate::sed::error_handling::{ScriptLocation, input_runtime_error};
+use crate::sed::debug;
+use crate::sed::error_handling::{ScriptLocation, input_runtime_error, runtime_error};
 use crate::sed::fast_io::{IOChunk, LineReader, OutputBuffer};
 use crate::sed::fast_regex::Regex;
 use crate::sed::in_place::InPlace;
@@ -22,6 +23,7 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use std::io::{self, IsTerminal};
 use std::path::PathBuf;
+use std::process::{self, Stdio};
 use std::rc::Rc;
 use uucore::display::Quotable;
 use uucore::error::{FromIo, UResult, set_exit_code};
@@ -65,6 +67,23 @@ fn match_address(
             }
         }
 
+        AddressType::Step => {
+            if let AddressValue::Step(first, step) = addr.value {
+                let lineno = context.line_number;
+                Ok(lineno >= first && (lineno - first).is_multiple_of(step))
+            } else {
+                Ok(false)
+            }
+        }
+
+        AddressType::Multiple => {
+            if let AddressValue::LineNumber(n) = addr.value {
+                Ok(context.line_number.is_multiple_of(n))
+            } else {
+                Ok(false)
+            }
+        }
+
         // Recognize "$" as the last line of last file. This is consistent
         // with the original 7th Research Edition implementation:
         // https://github.com/dspinellis/unix-history-repo/blob/Research-V7/usr/src/cmd/sed/sed1.c#L665
@@ -143,6 +162,16 @@ fn applies(
                             command.start_line = Some(linenum);
                         }
                     }
+                    AddressType::Multiple => {
+                        // As in GNU sed, a range starting at a multiple
+                        // ends at the next one; a 0 multiple selects a
+                        // single line.
+                        if let AddressValue::LineNumber(0) = addr2.value {
+                            context.last_address = true;
+                        } else {
+                            command.start_line = Some(linenum);
+                        }
+                    }
                     _ => {
                         command.start_line = Some(linenum);
                     }
@@ -280,7 +309,10 @@ fn substitute(
                     }
                     result.push_str(&text.unwrap()[last_end..m.start()]);
 
-                    if sub.occurrence == 0 || count == sub.occurrence {
+                    if sub.occurrence == 0
+                        || count == sub.occurrence
+                        || (sub.global && count > sub.occurrence)
+                    {
                         let replacement = sub.replacement.apply_captures(command, &caps)?;
                         result.push_str(&replacement);
                         replaced = true;
@@ -293,7 +325,7 @@ fn substitute(
 
                     // Early exit if only a specific occurrence,
                     // (likely 1) needed replacing.
-                    if count == sub.occurrence {
+                    if count == sub.occurrence && !sub.global {
                         break 'captures Ok(());
                     }
                 }
@@ -313,13 +345,19 @@ fn substitute(
 
         pattern.set_to_string(result, pattern.is_newline_terminated());
 
+        if sub.execute {
+            execute_pattern(pattern, &command.location)?;
+        }
+
         if sub.print_flag {
             write_chunk(output, context, pattern)?;
         }
 
         // Write to file if needed.
         if let Some(ref writer) = sub.write_file {
-            writer.borrow_mut().write_line(pattern.as_str()?)?;
+            writer
+                .borrow_mut()
+                .write_line(output, pattern.as_str()?, &command.location)?;
         }
         context.substitution_made = true;
     }
@@ -327,6 +365,32 @@ fn substitute(
     Ok(())
 }
 
+/// Run the specified command through the shell and return its output.
+/// As in GNU sed, the command inherits the standard input.
+fn run_shell(command: &str, location: &ScriptLocation) -> UResult<String> {
+    let result = process::Command::new("/bin/sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| {
+            runtime_error::<()>(location, format!("running {}: {e}", command.quote())).unwrap_err()
+        })?;
+    Ok(String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+/// Replace the pattern space with the output of executing it as a command.
+/// As in GNU sed, a trailing newline of the output is removed.
+fn execute_pattern(pattern: &mut IOChunk, location: &ScriptLocation) -> UResult<()> {
+    let mut result = run_shell(pattern.as_str()?, location)?;
+    if result.ends_with('\n') {
+        result.pop();
+    }
+    pattern.set_to_string(result, pattern.is_newline_terminated());
+    Ok(())
+}
+
 /// Apply the specified transliteration in the provided pattern space.
 fn transliterate(pattern: &mut IOChunk, trans: &Transliteration) -> UResult<()> {
     let text = pattern.as_str()?;
@@ -352,6 +416,7 @@ fn transliterate(pattern: &mut IOChunk, trans: &Transliteration) -> UResult<()>
 
 /// Output any data queued for output at the end of the cycle.
 fn flush_appends(output: &mut OutputBuffer, context: &mut ProcessingContext) -> UResult<()> {
+    let separator = context.separator();
     for elem in &context.append_elements {
         match elem {
             AppendElement::Text(text) => {
@@ -360,6 +425,9 @@ fn flush_appends(output: &mut OutputBuffer, context: &mut ProcessingContext) ->
             AppendElement::Path(path) => {
                 output.copy_file(path)?;
             }
+            AppendElement::Line(line) => {
+                output.write_str(format!("{line}{separator}"))?;
+            }
         }
     }
     context.append_elements.clear();
@@ -367,11 +435,17 @@ fn flush_appends(output: &mut OutputBuffer, context: &mut ProcessingContext) ->
 }
 
 /// List the passed pattern space in unambiguous form.
-fn list(output: &mut OutputBuffer, line: &IOChunk, max_width: usize) -> UResult<()> {
+/// Output lines are terminated by the specified separator.
+fn list(
+    output: &mut OutputBuffer,
+    line: &IOChunk,
+    max_width: usize,
+    separator: char,
+) -> UResult<()> {
     // Special case for an empty pattern space
     if line.is_empty() {
         if line.is_newline_terminated() {
-            output.write_str("$\n")?;
+            output.write_str(format!("${separator}"))?;
         }
         return Ok(());
     }
@@ -381,10 +455,12 @@ fn list(output: &mut OutputBuffer, line: &IOChunk, max_width: usize) -> UResult<
     let mut line_width = 0;
 
     for ch in line.chars() {
-        if ch == '\n' {
-            buff.push_str("$\n");
+        if ch == separator {
+            buff.push('$');
+            buff.push(separator);
             output.write_str(&buff)?;
             line_width = 0;
+            buff.clear();
             continue;
         }
 
@@ -394,6 +470,7 @@ fn list(output: &mut OutputBuffer, line: &IOChunk, max_width: usize) -> UResult<
             '\x08' => Cow::Borrowed(r"\b"),
             '\x0b' => Cow::Borrowed(r"\v"),
             '\x0c' => Cow::Borrowed(r"\f"),
+            '\n' => Cow::Borrowed(r"\n"),
             '\\' => Cow::Borrowed(r"\\"),
             '\r' => Cow::Borrowed(r"\r"),
             '\t' => Cow::Borrowed(r"\t"),
@@ -404,9 +481,11 @@ fn list(output: &mut OutputBuffer, line: &IOChunk, max_width: usize) -> UResult<
         };
 
         // See if folding is required before adding out_str and terminator.
+        // As in GNU sed, widths of 0 and 1 disable folding.
         let out_len = out_str.len();
-        if line_width + out_len + 1 > max_width {
-            buff.push_str("\\\n");
+        if max_width > 1 && line_width + out_len + 1 > max_width {
+            buff.push('\\');
+            buff.push(separator);
             output.write_str(&buff)?;
             line_width = 0;
             buff.clear();
@@ -416,7 +495,8 @@ fn list(output: &mut OutputBuffer, line: &IOChunk, max_width: usize) -> UResult<
     }
 
     if !buff.is_empty() {
-        buff.push_str("$\n");
+        buff.push('$');
+        buff.push(separator);
         output.write_str(buff)?;
     }
     Ok(())
@@ -430,6 +510,8 @@ fn process_file(
     output: &mut OutputBuffer,
     context: &mut ProcessingContext,
 ) -> UResult<()> {
+    let separator = context.separator();
+
     // Loop over the input lines as pattern space.
     'lines: while let Some((mut pattern, last_line)) = reader.get_line()? {
         context.last_line = last_line;
@@ -441,7 +523,7 @@ fn process_file(
                 // Continue processing the `N` command.
                 let current_line = pattern.as_str()?;
                 let mut combined_lines = action.prepend;
-                combined_lines.push('\n');
+                combined_lines.push(separator);
                 combined_lines.push_str(current_line);
 
                 pattern.set_to_string(combined_lines, pattern.is_newline_terminated());
@@ -451,11 +533,19 @@ fn process_file(
                 commands.clone()
             };
 
+        if context.debug {
+            debug::trace_cycle_start(output, context, &pattern)?;
+        }
+
         // Loop over script commands.
         while let Some(command_rc) = current.clone() {
             let mut command = command_rc.borrow_mut();
 
-            if !applies(&mut command, &mut pattern, context)? {
+            let matched = applies(&mut command, &mut pattern, context)?;
+            if context.debug {
+                debug::trace_command(output, context, &command, matched)?;
+            }
+            if !matched {
                 // Advance to next command
                 current = command.next.clone();
                 continue;
@@ -507,7 +597,7 @@ fn process_file(
                 }
                 'D' => {
                     // Delete up to \n and start a new cycle without new input.
-                    if let Some(pos) = pattern.as_str()?.find('\n') {
+                    if let Some(pos) = pattern.as_str()?.find(separator) {
                         let (s, _) = pattern.fields_mut()?;
                         s.drain(..=pos);
                         current = commands.clone();
@@ -518,6 +608,21 @@ fn process_file(
                         break;
                     }
                 }
+                'e' => match extract_variant!(command, ShellCommand) {
+                    // Output the result of executing the specified command.
+                    Some(shell_command) => {
+                        output.write_str(run_shell(shell_command, &command.location)?)?;
+                        if context.unbuffered {
+                            output.flush()?;
+                        }
+                    }
+                    // Execute the pattern space, replacing it with the result.
+                    None => execute_pattern(&mut pattern, &command.location)?,
+                },
+                'F' => {
+                    // Output the current input file name.
+                    output.write_str(format!("{}{separator}", context.input_name))?;
+                }
                 'g' => {
                     // Replace pattern with the contents of the hold space.
                     pattern.set_to_string(context.hold.content.clone(), context.hold.has_newline);
@@ -525,7 +630,7 @@ fn process_file(
                 'G' => {
                     // Append to pattern \n followed by hold space contents.
                     let (pat_content, pat_has_newline) = pattern.fields_mut()?;
-                    pat_content.push('\n');
+                    pat_content.push(separator);
                     pat_content.push_str(&context.hold.content);
                     *pat_has_newline = context.hold.has_newline;
                 }
@@ -536,7 +641,7 @@ fn process_file(
                 }
                 'H' => {
                     // Append to hold \n followed by pattern space contents.
-                    context.hold.content.push('\n');
+                    context.hold.content.push(separator);
                     context.hold.content.push_str(pattern.as_str()?);
                     context.hold.has_newline = pattern.is_newline_terminated();
                 }
@@ -547,7 +652,7 @@ fn process_file(
                 }
                 'l' => {
                     let width = *extract_variant!(command, Number);
-                    list(output, &pattern, width)?;
+                    list(output, &pattern, width, separator)?;
                 }
                 'n' => {
                     break;
@@ -571,13 +676,13 @@ fn process_file(
                 'P' => {
                     // Output pattern space, up to the first \n.
                     let line = pattern.as_str()?;
-                    match line.find('\n') {
+                    match line.find(separator) {
                         Some(pos) => {
                             output.write_str(&line[..=pos])?;
                         }
                         None => {
                             output.write_str(line)?;
-                            output.write_str("\n")?;
+                            output.write_str(separator)?;
                         }
                     }
                 }
@@ -601,6 +706,13 @@ fn process_file(
                         .append_elements
                         .push(AppendElement::Path(path.clone()));
                 }
+                'R' => {
+                    // Queue the file's next line for output at a later point.
+                    let reader = extract_variant!(command, NamedReader);
+                    if let Some(line) = reader.borrow_mut().read_line()? {
+                        context.append_elements.push(AppendElement::Line(line));
+                    }
+                }
                 's' => {
                     substitute(&mut pattern, &command, context, output)?;
                 }
@@ -619,10 +731,44 @@ fn process_file(
                         break;
                     }
                 }
+                'T' if context.substitution_made => {
+                    // No branch; reset the flag as t does when branching.
+                    context.substitution_made = false;
+                }
+                'T' => {
+                    // Branch to the specified label or end if none is given
+                    // if no substitution was made since last cycle or t/T.
+                    let target = extract_variant!(command, BranchTarget);
+                    if target.is_some() {
+                        // New command to execute
+                        current = target.clone();
+                        continue;
+                    } else {
+                        // Branch to the end of the script.
+                        break;
+                    }
+                }
+                'v' => {
+                    // Version requirement checked at compile time; do nothing.
+                }
                 'w' => {
                     // Append the pattern space to the specified file.
                     let writer = extract_variant!(command, NamedWriter);
-                    writer.borrow_mut().write_line(pattern.as_str()?)?;
+                    writer
+                        .borrow_mut()
+                        .write_line(output, pattern.as_str()?, &command.location)?;
+                }
+                'W' => {
+                    // Append the pattern space, up to the first \n, to the file.
+                    let writer = extract_variant!(command, NamedWriter);
+                    let line = pattern.as_str()?;
+                    let line = match line.find(separator) {
+                        Some(pos) => &line[..pos],
+                        None => line,
+                    };
+                    writer
+                        .borrow_mut()
+                        .write_line(output, line, &command.location)?;
                 }
                 'x' => {
                     // Exchange the contents of the pattern and hold spaces.
@@ -634,20 +780,32 @@ fn process_file(
                     let trans = extract_variant!(command, Transliteration);
                     transliterate(&mut pattern, trans)?;
                 }
+                'z' => {
+                    // Empty the pattern space, keeping its line ending.
+                    let (pat_content, _) = pattern.fields_mut()?;
+                    pat_content.clear();
+                }
                 ':' => {
                     // Branch target; do nothing.
                 }
                 '=' => {
                     // Output current line number.
-                    output.write_str(format!("{}\n", context.line_number))?;
+                    output.write_str(format!("{}{separator}", context.line_number))?;
                 }
                 // The compilation should supply only valid codes.
                 _ => panic!("invalid command code"),
             } // match
+            if context.debug {
+                debug::trace_spaces(output, context, &pattern, command.code)?;
+            }
             // Advance to next command.
             current = command.next.clone();
         }
 
+        if context.debug {
+            debug::trace_cycle_end(output, context)?;
+        }
+
         if !context.quiet {
             write_chunk(output, context, &pattern)?;
         }
@@ -659,13 +817,14 @@ fn process_file(
         }
     }
 
-    // Handle any N command remains.
+    // Handle any N command remains; these do not carry over to the next
+    // separate file.
     if context.separate
-        && !context.quiet
         && let Some(action) = context.input_action.take()
+        && !context.quiet
     {
         let mut pending = action.prepend;
-        pending.push('\n');
+        pending.push(separator);
         output.write_str(pending)?;
         if context.unbuffered {
             output.flush()?;
@@ -675,27 +834,64 @@ fn process_file(
     Ok(())
 }
 
+/// Reset the address ranges of the specified commands and of the
+/// blocks they contain, as when starting a separate file.
+fn reset_addresses(mut cur: Option<Rc<RefCell<Command>>>, end: &Option<Rc<RefCell<Command>>>) {
+    while let Some(rc_cmd) = cur {
+        if let Some(end_cmd) = end
+            && Rc::ptr_eq(&rc_cmd, end_cmd)
+        {
+            break;
+        }
+
+        let mut cmd = rc_cmd.borrow_mut();
+        cmd.reset_range();
+        if cmd.code == '{' {
+            // The block's last command continues with the following one.
+            let block_end = cmd.next.clone().or(end.clone());
+            if let CommandData::BranchTarget(Some(body)) = &cmd.data {
+                reset_addresses(Some(body.clone()), &block_end);
+            }
+        }
+        cur = cmd.next.clone();
+    }
+}
+
 /// Process all input files
 pub fn process_all_files(
     commands: Option<Rc<RefCell<Command>>>,
     files: Vec<PathBuf>,
     context: &mut ProcessingContext,
 ) -> UResult<()> {
+    // Read minimal input only when explicitly asked with -u.
+    let minimal_input = context.unbuffered;
     context.unbuffered = context.unbuffered || io::stdout().is_terminal();
 
+    if context.debug {
+        debug::trace_program(&commands)?;
+    }
+
     let mut in_place = InPlace::new(context.clone());
     let last_file_index = files.len() - 1;
 
     for (index, path) in files.iter().enumerate() {
         context.last_file = index == last_file_index;
-        let mut reader = LineReader::open(path)
-            .map_err_context(|| format!("error opening input file {}", path.quote()))?;
+        let separator = context.separator() as u8;
+        let reader = if minimal_input {
+            LineReader::open_minimal(path, separator)
+        } else {
+            LineReader::open(path, separator)
+        };
+        let mut reader =
+            reader.map_err_context(|| format!("error opening input file {}", path.quote()))?;
+        reader.set_lookahead(context.needs_last_line);
         let output = in_place.begin(path)?;
 
         if context.separate {
             context.line_number = 0;
+            reset_addresses(commands.clone(), &None);
         }
-        context.input_name = path.quote().to_string();
+        context.input_name = path.to_string_lossy().to_string();
         process_file(&commands, &mut reader, output, context)?;
 
         // Handle any N command remains.
@@ -705,7 +901,7 @@ pub fn process_all_files(
             && let Some(action) = context.input_action.take()
         {
             let mut pending = action.prepend;
-            pending.push('\n');
+            pending.push(context.separator());
             output.write_str(pending)?;
         }
 
@@ -196,6 +196,7 @@ macro_rules! check_output_posix {
 const LINES1: &str = "input/lines1";
 const LINES2: &str = "input/lines2";
 const NO_NEW_LINE: &str = "input/no-new-line.txt";
+const NULL_DATA: &str = "input/null-data";
 
 ////////////////////////////////////////////////////////////
 // Comments
@@ -259,9 +260,117 @@ check_output!(
 );
 check_output!(addr_empty_re_reuse, ["-n", "/_2/,//p", LINES1, LINES2]);
 check_output!(addr_simple_negation, ["-e", r"4,12!s/^/^/", LINES1]);
+check_output!(addr_step, ["-n", "0~3p", LINES1]);
+check_output!(addr_step_negate, ["2~3!d", LINES1]);
+check_output!(addr_step_range_end, ["-n", "2,0~4p", LINES1]);
+check_output!(addr_step_range_start, ["-n", "1~5,+1p", LINES1]);
+check_output!(addr_step_zero, ["-n", "5~0p", LINES1]);
+check_output!(addr_multiple, ["-n", "5,~4p", LINES1]);
+check_output!(addr_multiple_at_start, ["-n", "4,~4p", LINES1]);
+check_output!(addr_multiple_at_start_two, ["-n", "2,~2p", LINES1]);
+check_output!(addr_multiple_one, ["-n", "1,~1p", LINES1]);
+check_output!(addr_multiple_zero, ["-n", "5,~0p", LINES1]);
+check_output!(addr_pattern_to_multiple, ["-n", "/_1[01]/,~3p", LINES1]);
+check_output!(addr_multiline, ["-n", r"N;/^l1_4$/Mp", LINES1]);
+check_output!(addr_zero_to_pattern, ["-n", "0,/_3/p", LINES1]);
+check_output!(addr_zero_to_pattern_first, ["-n", "0,/l1_1/p", LINES1]);
+check_output!(addr_zero_separate, ["-s", "-n", "0,/_2/p", LINES1, LINES2]);
+
+#[test]
+fn test_addr_zero_first_line_not_reached() {
+    new_ucmd!()
+        .args(&["1d;0,/x/s//y/"])
+        .pipe_in("a\nx\nx\n")
+        .succeeds()
+        .stdout_is("y\nx\n");
+}
+
+#[test]
+fn test_addr_zero_separate_in_block() {
+    new_ucmd!()
+        .args(&["-s", "-n", "/_/{0,/_/s//X/p\n}", LINES1, LINES2])
+        .succeeds()
+        .stdout_is("l1X1\nl2X1\n");
+}
+
+#[test]
+fn test_addr_zero_without_re() {
+    new_ucmd!()
+        .args(&["-n", "0,5p", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is("sed: <script argument 1>:1:4: error: invalid usage of line address 0\n");
+}
 
 ////////////////////////////////////////////////////////////
 // Substitution: s
+check_output!(subst_interval, ["-e", r"s/[0-9]\{2\}/X/", LINES1]);
+check_output!(subst_interval_min, ["-e", r"s/l\{1,\}/X/g", LINES1]);
+check_output!(subst_interval_max, ["-e", r"s/_[0-9]\{,1\}$/X/", LINES1]);
+check_output!(
+    subst_interval_group,
+    ["-e", r"s/\(l1\)\{1,2\}_\([0-9]\)\{2,3\}/<\2>/", LINES1]
+);
+check_output!(
+    subst_gnu_bre_operators,
+    ["-e", r"s/l\+1\?_\(1\|3\)/X/", LINES1]
+);
+check_output!(
+    regex_word_anchors,
+    ["-e", r"s/\<l/L/g;s/[0-9]\>/#/", LINES1]
+);
+check_output!(
+    regex_word_boundaries,
+    ["-E", "-e", r"s/\b/|/g;s/\B1/+/", LINES1]
+);
+check_output!(regex_word_classes, ["-e", r"s/\w\w/<&>/;s/\W*$/!/", LINES1]);
+
+#[test]
+fn test_gnu_regex_escapes_posix() {
+    new_ucmd!()
+        .args(&["--posix", "-e", r"s/\<a\>\w/X/;s/\`\'/Y/"])
+        .pipe_in("<a>w `'\n")
+        .succeeds()
+        .stdout_is("X Y\n");
+}
+
+#[test]
+fn test_gnu_bre_operators_posix() {
+    new_ucmd!()
+        .args(&["--posix", "-e", r"s/a\+b\?\|c/X/"])
+        .pipe_in("aa+b?|c\n")
+        .succeeds()
+        .stdout_is("aX\n");
+}
+
+// Alternation is leftmost-first rather than leftmost-longest.
+#[test]
+fn test_gnu_bre_alternation_leftmost_first() {
+    new_ucmd!()
+        .args(&["-e", r"s/a*\|b/X/g"])
+        .pipe_in("b\n")
+        .succeeds()
+        .stdout_is("XbX\n");
+}
+
+#[test]
+fn test_interval_invalid_content() {
+    new_ucmd!()
+        .args(&["-e", r"s/a\{3,x\}/b/", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is("sed: <script argument 1>:1:8: error: Invalid content of \\{\\}\n");
+}
+
+#[test]
+fn test_interval_unmatched() {
+    new_ucmd!()
+        .args(&["-e", r"1d;s/a\{3/b/", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is("sed: <script argument 1>:1:10: error: Unmatched \\{\n");
+}
+
 check_output!(subst_any, ["-e", r"s/./X/g", LINES1]);
 check_output!(subst_any_global, ["-e", r"s,.,X,g", LINES1]);
 check_output!(subst_escaped_magic_separator, ["-e", r"s.\..X.g", LINES1]);
@@ -288,10 +397,97 @@ check_output!(
 );
 check_output!(subst_multiline, ["-e", "s/_/u0\\\nu1\\\nu2/g", LINES1]);
 check_output!(subst_numbered_replacement, ["-e", r"s/./X/4", LINES1]);
+check_output!(subst_multiline_begin, ["-e", r"N;N;s/^l/L/Mg", LINES1]);
+check_output!(subst_multiline_end, ["-e", r"N;s/1$/#/Mg", LINES1]);
+check_output!(subst_multiline_re, ["-e", r"N;N;s/_.$/#/Mg", LINES1]);
+check_output!(
+    subst_buffer_anchors,
+    ["-e", r"N;N;s/\`l/</Mg;s/.\'/>/Mg", LINES1]
+);
+check_output!(
+    subst_case_conversion,
+    ["-e", r"s/\(l\)\(.*\)/\u\1_\U\2x\Ey\lZ/", LINES1]
+);
+#[test]
+fn subst_case_conversion_before_hex_digits() {
+    new_ucmd!()
+        .args(&[r"s/x/\ubeef \U00e9/"])
+        .pipe_in("x\n")
+        .succeeds()
+        .stdout_is("Beef 00E9\n");
+}
+
+check_output!(
+    subst_case_conversion_unicode,
+    ["-e", r"s/.*/\U&/", "input/unicode"]
+);
+check_output!(subst_numbered_global, ["-e", r"s/./X/3g", LINES1]);
+check_output!(
+    subst_numbered_global_groups,
+    ["-e", r"s/\(.\)/[\1]/g2", LINES1]
+);
 check_output!(subst_brace, ["-e", r"s/[123]/X/g", LINES1]);
 check_output!(subst_case_insensitive, ["-e", r"s/L/Line/", LINES1]);
 check_output!(subst_no_new_line, ["-e", r"s/l/L/g", NO_NEW_LINE]);
 check_output!(subst_re_reuse, ["-e", r"2s//M/;1s/l/L/", LINES1]);
+check_output!(
+    bracket_posix_classes,
+    [
+        "-n",
+        concat!(
+            "h;g;s/[^[:alnum:]]//g;l 0;g;s/[^[:alpha:]]//g;l 0;",
+            "g;s/[^[:blank:]]//g;l 0;g;s/[^[:cntrl:]]//g;l 0;",
+            "g;s/[^[:digit:]]//g;l 0;g;s/[^[:graph:]]//g;l 0;",
+            "g;s/[^[:lower:]]//g;l 0;g;s/[^[:print:]]//g;l 0;",
+            "g;s/[^[:punct:]]//g;l 0;g;s/[^[:space:]]//g;l 0;",
+            "g;s/[^[:upper:]]//g;l 0;g;s/[^[:xdigit:]]//g;l 0"
+        ),
+        "input/ascii"
+    ]
+);
+check_output!(
+    bracket_posix_classes_unicode,
+    [
+        "-e",
+        "s/[[:alpha:]]/a/g;s/[[:punct:]]/p/g;s/[[:space:]]/_/g",
+        "input/unicode"
+    ]
+);
+check_output!(
+    bracket_equivalence_collating,
+    ["-E", "-e", "s/[[=o=][.W.]]+/X/g", "input/unicode"]
+);
+check_output!(
+    bracket_edge_characters,
+    ["-n", "s/[]^-]/X/g;l 0", "input/ascii"]
+);
+
+#[test]
+fn bracket_literal_open_bracket_and_backslash() {
+    for (script, expected) in [
+        (r"s/[[]/X/g", "aXb\\c]d\n"),
+        (r"s/[a[]/X/g", "XXb\\c]d\n"),
+        (r"s/[\]/X/g", "a[bXc]d\n"),
+        (r"s/[a\]]/X/g", "a[b\\c]d\n"),
+        (r"s/[c\]]/X/g", "a[b\\Xd\n"),
+    ] {
+        new_ucmd!()
+            .args(&[script])
+            .pipe_in("a[b\\c]d\n")
+            .succeeds()
+            .stdout_is(expected);
+    }
+}
+
+check_output!(subst_dot_newline, ["-e", r"N;s/1.l/X/", LINES1]);
+check_output!(
+    subst_negated_bracket_newline,
+    ["-e", r"N;s/_[^_]*_/X/", LINES1]
+);
+check_output!(
+    subst_dot_newline_multiline,
+    ["-e", r"N;s/1.l/X/M;s/_[^_]*_/Y/M;s/.$/Z/M", LINES1]
+);
 check_output!(subst_newline_class, ["-n", r"1{;N;s/[\n]/X/;p;}", LINES1]);
 check_output!(subst_newline_re, ["-n", r"1{;N;s/\n/X/;p;}", LINES1]);
 
@@ -560,6 +756,45 @@ tb"#,
     ]
 );
 
+#[test]
+fn branch_test_negated() {
+    new_ucmd!()
+        .args(&["s/a/X/;T;s/$/!/"])
+        .pipe_in("ab\ncd\n")
+        .succeeds()
+        .stdout_is("Xb!\ncd\n");
+}
+
+// Check that T clears the substitution done flag when not branching
+#[test]
+fn branch_test_negated_clears() {
+    new_ucmd!()
+        .args(&["s/a/X/;T l1;T l2;b;:l1;s/^/ERROR/;b;:l2;s/^/ok/"])
+        .pipe_in("ab\ncd\n")
+        .succeeds()
+        .stdout_is("okXb\nERRORcd\n");
+}
+
+////////////////////////////////////////////////////////////
+// v command
+#[test]
+fn version_supported() {
+    new_ucmd!()
+        .args(&["v 4.2;p"])
+        .pipe_in("a\n")
+        .succeeds()
+        .stdout_is("a\na\n");
+}
+
+#[test]
+fn version_unsupported() {
+    new_ucmd!()
+        .args(&["v 99.1"])
+        .fails()
+        .code_is(1)
+        .stderr_is("sed: <script argument 1>:1:7: error: expected newer version of sed (requested 99.1, supported 4.9)\n");
+}
+
 ////////////////////////////////////////////////////////////
 // Text: a, c, i
 
@@ -709,6 +944,47 @@ check_output!(read_ok, [format!("4r {LINES2}"), LINES1.to_string()]);
 check_output!(read_missing, ["5r /xyzzyxyzy42", LINES1]);
 check_output!(read_empty, ["6r input/empty", LINES1]);
 
+#[test]
+fn read_line_interleave() {
+    new_ucmd!()
+        .args(&["R input/two-lines.txt"])
+        .pipe_in("a\nb\nc\n")
+        .succeeds()
+        .stdout_is("a\nline one\nb\nline two\nc\n");
+}
+
+#[test]
+fn read_line_shared_position() {
+    new_ucmd!()
+        .args(&[
+            "-e",
+            "1R input/two-lines.txt",
+            "-e",
+            "2R input/two-lines.txt",
+        ])
+        .pipe_in("a\nb\n")
+        .succeeds()
+        .stdout_is("a\nline one\nb\nline two\n");
+}
+
+#[test]
+fn read_line_missing_newline() {
+    new_ucmd!()
+        .args(&["R input/no-new-line.txt"])
+        .pipe_in("a\nb\n")
+        .succeeds()
+        .stdout_is("a\nHello\nb\n");
+}
+
+#[test]
+fn read_line_missing_file() {
+    new_ucmd!()
+        .args(&["R /xyzzyxyzy42"])
+        .pipe_in("a\n")
+        .succeeds()
+        .stdout_is("a\n");
+}
+
 #[test]
 fn write_single_file() -> std::io::Result<()> {
     let temp = NamedTempFile::new()?;
@@ -752,6 +1028,199 @@ fn write_two_files() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn write_same_file_twice() -> std::io::Result<()> {
+    let temp = NamedTempFile::new()?;
+    let cmd = format!("1w {0}\n3s/l/L/w {0}", temp.path().display());
+
+    new_ucmd!().args(&["-n", &cmd, LINES1]).succeeds();
+
+    assert_eq!(fs::read_to_string(temp.path())?, "l1_1\nL1_3\n");
+    Ok(())
+}
+
+#[test]
+fn write_unreached_keeps_file() -> std::io::Result<()> {
+    let mut temp = NamedTempFile::new()?;
+    temp.write_all(b"keep\n")?;
+    let cmd = format!("/nomatch/w {}", temp.path().display());
+
+    new_ucmd!().args(&["-n", &cmd, LINES1]).succeeds();
+
+    assert_eq!(fs::read_to_string(temp.path())?, "keep\n");
+    Ok(())
+}
+
+#[test]
+fn write_unreached_all_output_files_truncates() -> std::io::Result<()> {
+    let mut temp = NamedTempFile::new()?;
+    temp.write_all(b"keep\n")?;
+    let cmd = format!("/nomatch/w {}", temp.path().display());
+
+    new_ucmd!().args(&["-a", "-n", &cmd, LINES1]).succeeds();
+
+    assert_eq!(fs::read_to_string(temp.path())?, "");
+    Ok(())
+}
+
+#[test]
+fn write_first_line() -> std::io::Result<()> {
+    let temp = NamedTempFile::new()?;
+    let cmd = format!("N;W {}", temp.path().display());
+
+    new_ucmd!()
+        .args(&["-n", &cmd])
+        .pipe_in("a\nb\nc\nd\n")
+        .succeeds()
+        .no_stdout();
+
+    assert_eq!(fs::read_to_string(temp.path())?, "a\nc\n");
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////
+// Special file names
+#[test]
+fn write_dev_stdout_in_order() {
+    new_ucmd!()
+        .args(&["p;w /dev/stdout"])
+        .pipe_in("a\nb\n")
+        .succeeds()
+        .stdout_is("a\na\na\nb\nb\nb\n");
+}
+
+#[test]
+fn write_subst_dev_stdout() {
+    new_ucmd!()
+        .args(&["-n", "s/a/X/w /dev/stdout"])
+        .pipe_in("a\nb\n")
+        .succeeds()
+        .stdout_is("X\n");
+}
+
+#[test]
+fn write_first_line_dev_stderr() {
+    new_ucmd!()
+        .args(&["-n", "N;W /dev/stderr"])
+        .pipe_in("a\nb\n")
+        .succeeds()
+        .no_stdout()
+        .stderr_is("a\n");
+}
+
+#[test]
+fn read_dev_stdin() {
+    new_ucmd!()
+        .args(&["1r /dev/stdin", "input/two-lines.txt"])
+        .pipe_in("x\ny\n")
+        .succeeds()
+        .stdout_is("line one\nx\ny\nline two\n");
+}
+
+#[test]
+fn read_line_dev_stdin() {
+    new_ucmd!()
+        .args(&["R /dev/stdin", "input/two-lines.txt"])
+        .pipe_in("x\n")
+        .succeeds()
+        .stdout_is("line one\nx\nline two\n");
+}
+
+#[test]
+fn in_place_write_dev_stdout() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = assert_fs::TempDir::new()?;
+    let file = temp_dir.child("file.txt");
+    file.write_str("a\nb\n")?;
+
+    new_ucmd!()
+        .args(&["-i", "-e", "1w /dev/stdout", file.path().to_str().unwrap()])
+        .succeeds()
+        .stdout_is("a\n");
+
+    assert_eq!(fs::read_to_string(file.path())?, "a\nb\n");
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////
+// F and z commands
+#[test]
+fn file_name() {
+    new_ucmd!()
+        .args(&["-n", "1F;$F", LINES1, LINES2])
+        .succeeds()
+        .stdout_is("input/lines1\ninput/lines2\n");
+}
+
+#[test]
+fn file_name_stdin() {
+    new_ucmd!()
+        .args(&["F"])
+        .pipe_in("a\n")
+        .succeeds()
+        .stdout_is("-\na\n");
+}
+
+#[test]
+fn zap_pattern_space() {
+    new_ucmd!()
+        .args(&["2z;3{z;s/^/x/;}"])
+        .pipe_in("a\nb\nc\nd")
+        .succeeds()
+        .stdout_is("a\n\nx\nd");
+}
+
+////////////////////////////////////////////////////////////
+// e command and s///e flag
+#[cfg(unix)]
+#[test]
+fn exec_command() {
+    new_ucmd!()
+        .args(&["2e echo run; echo twice"])
+        .pipe_in("one\ntwo\n")
+        .succeeds()
+        .stdout_is("one\nrun\ntwice\ntwo\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_command_inherits_stdin() {
+    new_ucmd!()
+        .args(&["-n", "1e cat", LINES1])
+        .pipe_in("piped\n")
+        .succeeds()
+        .stdout_is("piped\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_pattern_space() {
+    new_ucmd!()
+        .args(&["1e"])
+        .pipe_in("echo one; echo two\nthree\n")
+        .succeeds()
+        .stdout_is("one\ntwo\nthree\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_subst_flag() {
+    new_ucmd!()
+        .args(&["-n", "s/^/echo x/ep"])
+        .pipe_in("a\nb\n")
+        .succeeds()
+        .stdout_is("xa\nxb\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_subst_flag_no_match() {
+    new_ucmd!()
+        .args(&["s/^z/echo x/e"])
+        .pipe_in("echo a\n")
+        .succeeds()
+        .stdout_is("echo a\n");
+}
+
 ////////////////////////////////////////////////////////////
 // =, l commands
 check_output!(number_continuous, ["/l2_/=", LINES1, LINES2]);
@@ -760,6 +1229,116 @@ check_output!(number_separate, ["-s", "/l._8/=", LINES1, LINES2]);
 check_output!(list_ascii, ["-n", "l 60", "input/ascii"]);
 check_output!(list_empty, ["-n", "l 60", "input/empty"]);
 check_output!(list_unicode, ["l 60", "input/unicode"]);
+check_output!(list_default, ["-n", "l", "input/ascii"]);
+check_output!(list_length, ["-n", "-l", "20", "l", "input/ascii"]);
+check_output!(list_no_wrap, ["-n", "l 0", "input/ascii"]);
+check_output!(list_length_no_wrap, ["-n", "-l", "0", "l", "input/ascii"]);
+check_output!(list_width_one, ["-n", "l 1", "input/ascii"]);
+
+////////////////////////////////////////////////////////////
+// NUL-separated data: -z
+check_output!(null_data_subst, ["-z", "s/^/>/", NULL_DATA]);
+check_output!(null_data_number, ["-z", "=", NULL_DATA]);
+check_output!(null_data_next_print_delete, ["-z", "$!N;P;D", NULL_DATA]);
+check_output!(null_data_hold_list, ["-nz", "1h;2G;3H;$g;l", NULL_DATA]);
+check_output!(null_data_insert, ["-z", "2i\\\ninserted", NULL_DATA]);
+
+#[test]
+fn null_data_stdin() {
+    new_ucmd!()
+        .args(&["-z", "s/^/>/"])
+        .pipe_in_fixture(NULL_DATA)
+        .succeeds()
+        .stdout_is_fixture("output/null_data_subst");
+}
+
+#[test]
+fn null_data_write_file() -> std::io::Result<()> {
+    let temp = NamedTempFile::new()?;
+    let cmd = format!("2,3w {}", temp.path().display());
+
+    new_ucmd!().args(&["-nz", &cmd, NULL_DATA]).succeeds();
+
+    let actual = fs::read(temp.path())?;
+    assert_eq!(actual, b"second\0third\0");
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////
+// Minimal input: -u
+#[test]
+fn unbuffered_leaves_remaining_input() -> std::io::Result<()> {
+    let input = fs::File::open("tests/fixtures/sed/input/lines1")?;
+    let mut remaining = input.try_clone()?;
+
+    new_ucmd!()
+        .args(&["-u", "2q"])
+        .set_stdin(input)
+        .succeeds()
+        .stdout_is("l1_1\nl1_2\n");
+
+    let mut rest = String::new();
+    remaining.read_to_string(&mut rest)?;
+    assert!(rest.starts_with("l1_3\nl1_4\n"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn unbuffered_leaves_remaining_piped_input() -> std::io::Result<()> {
+    let (input, mut writer) = std::io::pipe()?;
+    writer.write_all(b"one\ntwo\nthree\n")?;
+    drop(writer);
+    let mut remaining = input.try_clone()?;
+
+    new_ucmd!()
+        .args(&["-u", "1q"])
+        .set_stdin(input)
+        .succeeds()
+        .stdout_is("one\n");
+
+    let mut rest = String::new();
+    remaining.read_to_string(&mut rest)?;
+    assert_eq!(rest, "two\nthree\n");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn unbuffered_last_line_reads_ahead_on_pipe() -> std::io::Result<()> {
+    let (input, mut writer) = std::io::pipe()?;
+    writer.write_all(b"one\ntwo\nthree\n")?;
+    drop(writer);
+    let mut remaining = input.try_clone()?;
+
+    // Detecting the last line consumes the byte following the line.
+    new_ucmd!()
+        .args(&["-u", "1q;$d"])
+        .set_stdin(input)
+        .succeeds()
+        .stdout_is("one\n");
+
+    let mut rest = String::new();
+    remaining.read_to_string(&mut rest)?;
+    assert_eq!(rest, "wo\nthree\n");
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////
+// Annotated execution: --debug
+check_output!(
+    debug_trace,
+    ["--debug", "-n", "1,2{h;s/l/L/gp;};$G;2q", LINES1]
+);
+check_output!(
+    debug_program_listing,
+    [
+        "--debug",
+        "-n",
+        ":a\n/x/I!ba\n3,+2y/abc/xyz/;$ a\\\nend",
+        "/dev/null"
+    ]
+);
 
 ////////////////////////////////////////////////////////////
 // In-place editing
@@ -824,6 +1403,57 @@ fn in_place_edit_backup() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn in_place_edit_files_separately() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = assert_fs::TempDir::new()?;
+    let first = temp_dir.child("first.txt");
+    let second = temp_dir.child("second.txt");
+
+    first.write_str("1\n2\n3\n4\n")?;
+    second.write_str("a\nb\nc\n")?;
+
+    new_ucmd!()
+        .args(&[
+            "-i",
+            "-e",
+            "1d;$d;=",
+            first.path().to_str().unwrap(),
+            second.path().to_str().unwrap(),
+        ])
+        .succeeds();
+
+    assert_eq!(fs::read_to_string(first.path())?, "2\n2\n3\n3\n");
+    assert_eq!(fs::read_to_string(second.path())?, "2\nb\n");
+
+    Ok(())
+}
+
+#[test]
+fn in_place_edit_resets_pending_next() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = assert_fs::TempDir::new()?;
+    let first = temp_dir.child("first.txt");
+    let second = temp_dir.child("second.txt");
+
+    first.write_str("1\n2\n3\n")?;
+    second.write_str("a\nb\n")?;
+
+    new_ucmd!()
+        .args(&[
+            "-n",
+            "-i",
+            "-e",
+            "N;p",
+            first.path().to_str().unwrap(),
+            second.path().to_str().unwrap(),
+        ])
+        .succeeds();
+
+    assert_eq!(fs::read_to_string(first.path())?, "1\n2\n");
+    assert_eq!(fs::read_to_string(second.path())?, "a\nb\n");
+
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 fn in_place_edit_follow_symlink_edits_target() -> Result<(), Box<dyn std::error::Error>> {
@@ -983,6 +1613,91 @@ fn test_invalid_backreference() {
         .stderr_is("sed: <script argument 1>:1:8: error: invalid reference \\1 on command's RHS\n");
 }
 
+#[test]
+fn test_sandbox_read() {
+    new_ucmd!()
+        .args(&["--sandbox", "p;2r /dev/null", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:4: error: e/r/w commands disabled in sandbox mode\n",
+        );
+}
+
+#[test]
+fn test_sandbox_subst_write() {
+    new_ucmd!()
+        .args(&["--sandbox", "s/a/b/gw /dev/null", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:8: error: e/r/w commands disabled in sandbox mode\n",
+        );
+}
+
+#[test]
+fn test_sandbox_exec() {
+    new_ucmd!()
+        .args(&["--sandbox", "1e ls", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:2: error: e/r/w commands disabled in sandbox mode\n",
+        );
+}
+
+#[test]
+fn test_sandbox_read_line() {
+    new_ucmd!()
+        .args(&["--sandbox", "R /dev/null", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:1: error: e/r/w commands disabled in sandbox mode\n",
+        );
+}
+
+#[test]
+fn test_sandbox_write_first_line() {
+    new_ucmd!()
+        .args(&["--sandbox", "$W out", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:2: error: e/r/w commands disabled in sandbox mode\n",
+        );
+}
+
+#[test]
+fn test_sandbox_allows_other_commands() {
+    new_ucmd!()
+        .args(&["--sandbox", "-n", "$p", LINES1])
+        .succeeds()
+        .stdout_is("l1_14\n");
+}
+
+#[test]
+fn test_posix_rejects_exit_code() {
+    new_ucmd!()
+        .args(&["--posix", "$q1", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:3: error: an argument to the q command is not allowed with --posix\n",
+        );
+}
+
+#[test]
+fn test_posix_rejects_escape() {
+    new_ucmd!()
+        .args(&["--posix", "s/1/\\x41/", LINES1])
+        .fails()
+        .code_is(1)
+        .stderr_is(
+            "sed: <script argument 1>:1:6: error: the \\x escape is not allowed with --posix\n",
+        );
+}
+
 #[test]
 fn test_duplicate_label() {
     new_ucmd!()
@@ -1048,6 +1763,24 @@ fn test_write_file_failure() {
         .stderr_contains("sed: <script argument 1>:1:1: error: creating file '/xyzzy/xyzy':");
 }
 
+#[test]
+fn test_write_file_failure_shared_file() {
+    new_ucmd!()
+        .args(&["/nomatch/w /xyzzy/xyzy\n2w /xyzzy/xyzy", LINES1])
+        .fails()
+        .code_is(2)
+        .stderr_contains("sed: <script argument 1>:2:1: error: creating file '/xyzzy/xyzy':");
+}
+
+#[test]
+fn test_write_file_failure_all_output_files() {
+    new_ucmd!()
+        .args(&["-a", "$!d;s/x/y/w /xyzzy/xyzy", LINES1])
+        .fails()
+        .code_is(2)
+        .stderr_contains("sed: <script argument 1>:1:11: error: creating file '/xyzzy/xyzy':");
+}
+
 #[test]
 fn test_missing_substitute_re() {
     new_ucmd!()